@@ -1,19 +1,237 @@
+use crate::batch;
+use crate::dbus;
+use crate::exclusion::{Exclusion, PathPattern};
+use crate::filter::parse_filter;
+use crate::find_duplicates::SearchOptions;
 use crate::gtk_prelude::*;
 use crate::main_window::MainWindow;
+use crate::preferences::default_search_options;
+use crate::profiles;
+use crate::scheduler;
+use crate::size_range::parse_size_range;
+use glib::translate::ToGlibPtr;
+use std::error::Error;
+use std::ffi::CString;
+use std::path::PathBuf;
+
+fn add_options(app: &gtk::Application) {
+    let options = [
+        (
+            "profile",
+            glib::OptionArg::String,
+            "Use options of a saved profile",
+            "NAME",
+        ),
+        (
+            "run-profile",
+            glib::OptionArg::String,
+            "Search with a saved profile and print duplicates without opening a window",
+            "NAME",
+        ),
+        (
+            "session",
+            glib::OptionArg::Filename,
+            "Use options saved to a file",
+            "FILE",
+        ),
+        (
+            "exclude",
+            glib::OptionArg::StringArray,
+            "Exclude files matching the wildcard",
+            "PATTERN",
+        ),
+        (
+            "size",
+            glib::OptionArg::String,
+            "File size condition, e.g. +1M",
+            "SIZE",
+        ),
+        (
+            "filter",
+            glib::OptionArg::String,
+            "Filter expression",
+            "EXPRESSION",
+        ),
+        (
+            "max-depth",
+            glib::OptionArg::Int,
+            "Descend at most N levels below searched directories",
+            "N",
+        ),
+    ];
+    for (name, arg, description, arg_description) in options {
+        app.add_main_option(
+            name,
+            glib::Char(0),
+            glib::OptionFlags::NONE,
+            arg,
+            description,
+            Some(arg_description),
+        );
+    }
+    app.add_main_option(
+        "scan",
+        glib::Char::from(b's'),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "Start searching immediately",
+        None,
+    );
+    app.add_main_option(
+        "background",
+        glib::Char(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "Run scheduled scans without opening a window",
+        None,
+    );
+    app.set_option_context_parameter_string(Some("[DIRECTORY…]"));
+}
+
+/// Wildcards without a slash are matched against file names.
+fn exclusion_pattern(pattern: String) -> Result<Exclusion, Box<dyn Error>> {
+    glob::Pattern::new(&pattern)?;
+    let basename = !pattern.contains('/');
+    Ok(Exclusion::Pattern(PathPattern {
+        pattern,
+        case_sensitive: true,
+        basename,
+    }))
+}
+
+/// Options of the window overridden by a profile or a session file and then by other
+/// command-line options.
+fn command_line_options(
+    window: &MainWindow,
+    dict: &glib::VariantDict,
+    directories: Vec<PathBuf>,
+) -> Result<(SearchOptions, Option<String>), Box<dyn Error>> {
+    let profile: Option<String> = dict.lookup("profile")?;
+    let session: Option<PathBuf> = dict.lookup("session")?;
+
+    let mut options = if let Some(name) = &profile {
+        profiles::load(name)?
+    } else if let Some(path) = &session {
+        profiles::load_file(path)?
+    } else {
+        window
+            .search_options()
+            .unwrap_or_else(|_| default_search_options())
+    };
+
+    if !directories.is_empty() {
+        options.paths = directories;
+    }
+    if let Some(patterns) = dict.lookup::<Vec<String>>("exclude")? {
+        for pattern in patterns {
+            options.exclude.push(exclusion_pattern(pattern)?);
+        }
+    }
+    if let Some(size) = dict.lookup::<String>("size")? {
+        let size = parse_size_range(&size)?;
+        options.min_size = size.min;
+        options.max_size = size.max;
+    }
+    if let Some(filter) = dict.lookup::<String>("filter")? {
+        options.filter = parse_filter(&filter)?;
+    }
+    if let Some(depth) = dict.lookup::<i32>("max-depth")? {
+        options.max_depth = (depth > 0).then_some(depth as u32);
+    }
+    if options.paths.is_empty() {
+        options.paths.extend(std::env::current_dir());
+    }
+    Ok((options, profile))
+}
+
+/// Runs a profile for `--run-profile` in the invoked process, without registering the
+/// application.
+fn on_handle_local_options(dict: &glib::VariantDict) -> i32 {
+    let Ok(Some(name)) = dict.lookup::<String>("run-profile") else {
+        return -1;
+    };
+    match batch::run_profile(&name) {
+        Ok(()) => 0,
+        Err(error) => {
+            eprintln!("{}", error);
+            1
+        }
+    }
+}
+
+/// Prints the message to standard error of the process which has been invoked. It is another
+/// process than this one when the application is already running.
+fn printerr(command_line: &gio::ApplicationCommandLine, message: &str) {
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    // the message is passed as an argument, so it is not taken for a format
+    unsafe {
+        gio::ffi::g_application_command_line_printerr(
+            command_line.to_glib_none().0,
+            b"%s\n\0".as_ptr().cast(),
+            message.as_ptr(),
+        );
+    }
+}
+
+fn on_command_line(app: &gtk::Application, command_line: &gio::ApplicationCommandLine) -> i32 {
+    if command_line.options_dict().contains("background") {
+        return match scheduler::run_in_background() {
+            Ok(()) => 0,
+            Err(error) => {
+                printerr(command_line, &error.to_string());
+                1
+            }
+        };
+    }
+
+    let directories = command_line
+        .arguments()
+        .iter()
+        .skip(1)
+        .filter_map(|arg| command_line.create_file_for_arg(arg).path())
+        .collect();
+
+    let window = MainWindow::new(app);
+    match command_line_options(&window, &command_line.options_dict(), directories) {
+        Ok((options, profile)) => {
+            window.set_search_options(&options, profile);
+            window.show();
+            if command_line.options_dict().contains("scan") {
+                window.start_search();
+            }
+            0
+        }
+        Err(error) => {
+            printerr(command_line, &error.to_string());
+            window.destroy();
+            1
+        }
+    }
+}
+
+/// Opens a window searching in directories passed by a file manager.
+fn on_open(app: &gtk::Application, files: &[gio::File]) {
+    let window = MainWindow::new(app);
+    let mut options = window
+        .search_options()
+        .unwrap_or_else(|_| default_search_options());
+    options.paths = files.iter().filter_map(|file| file.path()).collect();
+    window.set_search_options(&options, None);
+    window.show();
+}
 
 pub fn create_application() -> gtk::Application {
     let app = gtk::Application::builder()
         .application_id("net.andy128k.FindDuplicates")
-        .flags(gio::ApplicationFlags::FLAGS_NONE)
+        .flags(gio::ApplicationFlags::HANDLES_COMMAND_LINE | gio::ApplicationFlags::HANDLES_OPEN)
         .build();
 
-    app.connect_activate(|app| {
-        let app_window = MainWindow::new(app);
-        if let Ok(directory) = std::env::current_dir() {
-            app_window.add_directory(&directory);
-        }
-        app_window.show();
-    });
+    add_options(&app);
+    app.connect_startup(dbus::register);
+    app.connect_startup(scheduler::start);
+    app.connect_handle_local_options(|_app, dict| on_handle_local_options(dict));
+    app.connect_command_line(on_command_line);
+    app.connect_open(|app, files, _hint| on_open(app, files));
 
     app
 }
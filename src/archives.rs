@@ -0,0 +1,229 @@
+use crate::find_duplicates::{Cancellation, FileHash, FileInfo};
+use flate2::read::GzDecoder;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use xz2::read::XzDecoder;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+    TarXz,
+}
+
+fn archive_kind(path: &Path) -> Option<ArchiveKind> {
+    let name = path.file_name()?.to_str()?.to_lowercase();
+    if name.ends_with(".zip") {
+        Some(ArchiveKind::Zip)
+    } else if name.ends_with(".tar") {
+        Some(ArchiveKind::Tar)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(ArchiveKind::TarGz)
+    } else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+        Some(ArchiveKind::TarXz)
+    } else {
+        None
+    }
+}
+
+pub fn is_archive(path: &Path) -> bool {
+    archive_kind(path).is_some()
+}
+
+#[derive(Clone, Debug)]
+pub struct ArchiveMember {
+    pub archive: PathBuf,
+    pub name: String,
+    /// Tar archives cannot be accessed randomly, so hashes of their members are computed while
+    /// listing.
+    hash: Option<FileHash>,
+}
+
+impl ArchiveMember {
+    /// Tells whether the content has to be read from the archive to get the hash.
+    pub fn needs_hash(&self) -> bool {
+        self.hash.is_none()
+    }
+}
+
+/// Path of an archive member in form of `archive!/member`.
+fn virtual_path(archive: &Path, member: &str) -> PathBuf {
+    let mut path = archive.as_os_str().to_owned();
+    path.push("!/");
+    path.push(member.trim_start_matches('/'));
+    path.into()
+}
+
+fn member_info(
+    archive: &FileInfo,
+    name: String,
+    size: u64,
+    modified: SystemTime,
+    hash: Option<FileHash>,
+) -> FileInfo {
+    FileInfo {
+        path: virtual_path(&archive.path, &name),
+        modified,
+        size,
+        // member does not occupy disk space by itself
+        disk_usage: 0,
+        device: archive.device,
+        inode: archive.inode,
+        uid: archive.uid,
+        gid: archive.gid,
+        mode: archive.mode,
+        nlink: 1,
+        hard_links: Vec::new(),
+        archive_member: Some(ArchiveMember {
+            archive: archive.path.clone(),
+            name,
+            hash,
+        }),
+        link_target: None,
+    }
+}
+
+fn list_zip(archive: &FileInfo) -> io::Result<Vec<FileInfo>> {
+    let file = BufReader::new(File::open(&archive.path)?);
+    let mut zip = zip::ZipArchive::new(file)?;
+    let mut members = Vec::new();
+    for index in 0..zip.len() {
+        let entry = zip.by_index_raw(index)?;
+        if entry.is_file() {
+            members.push(member_info(
+                archive,
+                entry.name().to_owned(),
+                entry.size(),
+                archive.modified,
+                None,
+            ));
+        }
+    }
+    Ok(members)
+}
+
+/// Reader which fails once the search is cancelled, so a large archive is not read to the end.
+struct Cancellable<'c, R> {
+    reader: R,
+    cancellation: &'c Cancellation,
+}
+
+impl<R: Read> Read for Cancellable<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.cancellation.check()?;
+        self.reader.read(buf)
+    }
+}
+
+fn list_tar(
+    archive: &FileInfo,
+    reader: impl Read,
+    cancellation: &Cancellation,
+) -> io::Result<Vec<FileInfo>> {
+    let mut tar = tar::Archive::new(Cancellable {
+        reader,
+        cancellation,
+    });
+    let mut members = Vec::new();
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let size = entry.header().size()?;
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(entry.header().mtime()?);
+
+        let mut hasher = Sha256::new();
+        io::copy(&mut entry, &mut hasher)?;
+
+        members.push(member_info(
+            archive,
+            name,
+            size,
+            modified,
+            Some(hasher.finalize()),
+        ));
+    }
+    Ok(members)
+}
+
+/// Lists regular files stored in the archive. Members of tar archives are hashed while listing,
+/// which stops with an error once `cancellation` is cancelled.
+pub fn list_members(archive: &FileInfo, cancellation: &Cancellation) -> io::Result<Vec<FileInfo>> {
+    let open = || File::open(&archive.path).map(BufReader::new);
+    match archive_kind(&archive.path) {
+        Some(ArchiveKind::Zip) => list_zip(archive),
+        Some(ArchiveKind::Tar) => list_tar(archive, open()?, cancellation),
+        Some(ArchiveKind::TarGz) => list_tar(archive, GzDecoder::new(open()?), cancellation),
+        Some(ArchiveKind::TarXz) => list_tar(archive, XzDecoder::new(open()?), cancellation),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn hash_zip(archive: &Path, members: &mut [(&Path, &mut ArchiveMember)]) -> io::Result<()> {
+    let file = BufReader::new(File::open(archive)?);
+    let mut zip = zip::ZipArchive::new(file)?;
+    for (_, member) in members {
+        // compression methods and encryption which are not supported fail here
+        let Ok(mut entry) = zip.by_name(&member.name) else {
+            continue;
+        };
+        let mut hasher = Sha256::new();
+        if io::copy(&mut entry, &mut hasher).is_ok() {
+            member.hash = Some(hasher.finalize());
+        }
+    }
+    Ok(())
+}
+
+/// Hashes members of zip archives, reading the central directory of every archive once.
+/// Returns paths of members which cannot be read.
+pub fn hash_members(
+    files: Vec<&mut FileInfo>,
+    cancellation: &Cancellation,
+) -> io::Result<HashSet<PathBuf>> {
+    let mut by_archive: HashMap<PathBuf, Vec<(&Path, &mut ArchiveMember)>> = HashMap::new();
+    for fi in files {
+        if let Some(member) = fi
+            .archive_member
+            .as_mut()
+            .filter(|member| member.needs_hash())
+        {
+            by_archive
+                .entry(member.archive.clone())
+                .or_default()
+                .push((&fi.path, member));
+        }
+    }
+    let mut unreadable = HashSet::new();
+    for (archive, mut members) in by_archive {
+        cancellation.check()?;
+        // unreadable archive is skipped as while listing
+        let _ = hash_zip(&archive, &mut members);
+        for (path, member) in members {
+            if member.needs_hash() {
+                unreadable.insert(path.to_path_buf());
+            }
+        }
+    }
+    Ok(unreadable)
+}
+
+/// Hash of decompressed content of the member.
+pub fn member_hash(member: &ArchiveMember) -> io::Result<FileHash> {
+    if let Some(hash) = member.hash {
+        return Ok(hash);
+    }
+    let file = BufReader::new(File::open(&member.archive)?);
+    let mut zip = zip::ZipArchive::new(file)?;
+    let mut entry = zip.by_name(&member.name)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut entry, &mut hasher)?;
+    Ok(hasher.finalize())
+}
@@ -0,0 +1,396 @@
+use crate::find_duplicates::{Cancellation, ContentCache, FileInfo};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io;
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::DecoderOptions,
+    errors::Error as SymphoniaError,
+    formats::{FormatOptions, FormatReader},
+    io::MediaSourceStream,
+    meta::{MetadataOptions, MetadataRevision, StandardTagKey},
+    probe::Hint,
+};
+
+const AUDIO_EXTENSIONS: [&str; 8] = ["mp3", "flac", "ogg", "oga", "wav", "m4a", "aac", "mka"];
+
+/// Tracks which durations differ less than this are considered the same.
+const DURATION_TOLERANCE: f64 = 2.0;
+
+/// Audio is mixed down to mono and downsampled to about this rate before fingerprinting.
+const FINGERPRINT_RATE: u32 = 5512;
+/// Frames of a fingerprint per second.
+const FRAMES_PER_SECOND: u32 = 10;
+/// Bands between 300 Hz and 2 kHz, where lossy codecs keep most of the signal.
+const BANDS: usize = 17;
+const LOWEST_BAND: f64 = 300.0;
+const HIGHEST_BAND: f64 = 2000.0;
+/// Frames by which fingerprints are shifted against each other, so the delay added by encoders
+/// does not matter.
+const MAX_SHIFT: usize = 3;
+/// Fingerprints which differ in less than this share of bits are of the same recording.
+const MAX_BIT_ERROR_RATE: f64 = 0.2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AudioMatch {
+    /// Audio files are compared byte by byte as any other file.
+    Exact,
+    /// Audio files are compared by how they sound, so tags and encoding do not matter.
+    Content,
+    /// Audio files are compared by artist, title and duration.
+    Tags,
+}
+
+impl AudioMatch {
+    pub const ALL: [AudioMatch; 3] = [Self::Exact, Self::Content, Self::Tags];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Exact => "exact",
+            Self::Content => "decoded content",
+            Self::Tags => "artist, title and duration",
+        }
+    }
+}
+
+pub fn is_audio(fi: &FileInfo) -> bool {
+    fi.path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            AUDIO_EXTENSIONS
+                .iter()
+                .any(|known| ext.eq_ignore_ascii_case(known))
+        })
+}
+
+struct Track {
+    format: Box<dyn FormatReader>,
+    artist: Option<String>,
+    title: Option<String>,
+}
+
+fn read_tags(revision: &MetadataRevision, track: &mut Track) {
+    for tag in revision.tags() {
+        let value = || Some(tag.value.to_string().trim().to_lowercase());
+        match tag.std_key {
+            Some(StandardTagKey::Artist) => track.artist = track.artist.take().or_else(value),
+            Some(StandardTagKey::TrackTitle) => track.title = track.title.take().or_else(value),
+            _ => {}
+        }
+    }
+}
+
+fn open(fi: &FileInfo) -> Option<Track> {
+    let file = File::open(&fi.path).ok()?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = fi.path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let mut probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .ok()?;
+
+    let mut track = Track {
+        format: probed.format,
+        artist: None,
+        title: None,
+    };
+    if let Some(revision) = track.format.metadata().current().cloned() {
+        read_tags(&revision, &mut track);
+    }
+    if let Some(revision) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
+        read_tags(revision, &mut track);
+    }
+    Some(track)
+}
+
+/// Band-pass filter of the band around `frequency`.
+struct Band {
+    b0: f64,
+    a1: f64,
+    a2: f64,
+    x1: f64,
+    x2: f64,
+    y1: f64,
+    y2: f64,
+}
+
+impl Band {
+    fn new(frequency: f64, rate: f64) -> Self {
+        const Q: f64 = 8.0;
+        let w0 = 2.0 * PI * frequency / rate;
+        let alpha = w0.sin() / (2.0 * Q);
+        let a0 = 1.0 + alpha;
+        Self {
+            b0: alpha / a0,
+            a1: -2.0 * w0.cos() / a0,
+            a2: (1.0 - alpha) / a0,
+            x1: 0.0,
+            x2: 0.0,
+            y1: 0.0,
+            y2: 0.0,
+        }
+    }
+
+    /// Filters the next sample.
+    fn next(&mut self, x: f64) -> f64 {
+        let y = self.b0 * (x - self.x2) - self.a1 * self.y1 - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+/// Builds a fingerprint from downsampled mono samples. Every frame gets a bit per pair of
+/// neighbouring bands, telling whether the difference of their energies grew since the
+/// previous frame. Lossy encoding changes energies slightly but rarely flips these bits.
+struct Fingerprinter {
+    bands: Vec<Band>,
+    frame_length: usize,
+    samples: usize,
+    energies: [f64; BANDS],
+    previous: Option<[f64; BANDS]>,
+    frames: Vec<u16>,
+}
+
+impl Fingerprinter {
+    fn new(rate: u32) -> Self {
+        let step = (HIGHEST_BAND / LOWEST_BAND).powf(1.0 / (BANDS - 1) as f64);
+        Self {
+            bands: (0..BANDS)
+                .map(|band| Band::new(LOWEST_BAND * step.powi(band as i32), rate as f64))
+                .collect(),
+            frame_length: (rate / FRAMES_PER_SECOND).max(1) as usize,
+            samples: 0,
+            energies: [0.0; BANDS],
+            previous: None,
+            frames: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, sample: f64) {
+        for (band, energy) in self.bands.iter_mut().zip(self.energies.iter_mut()) {
+            let y = band.next(sample);
+            *energy += y * y;
+        }
+        self.samples += 1;
+        if self.samples < self.frame_length {
+            return;
+        }
+        if let Some(previous) = self.previous {
+            let mut bits = 0;
+            for band in 0..BANDS - 1 {
+                let now = self.energies[band] - self.energies[band + 1];
+                let before = previous[band] - previous[band + 1];
+                bits = (bits << 1) | (now > before) as u16;
+            }
+            self.frames.push(bits);
+        }
+        self.previous = Some(self.energies);
+        self.energies = [0.0; BANDS];
+        self.samples = 0;
+    }
+}
+
+/// Acoustic fingerprint of a track. Re-encoded and re-tagged copies of a track have close
+/// fingerprints.
+#[derive(Clone, Debug)]
+pub struct Fingerprint {
+    frames: Vec<u16>,
+}
+
+impl Fingerprint {
+    /// Share of different bits of the fingerprints at the best alignment of them.
+    fn bit_error_rate(&self, other: &Self) -> f64 {
+        let compare = |a: &[u16], b: &[u16]| {
+            let length = a.len().min(b.len());
+            let errors: u32 = a.iter().zip(b).map(|(a, b)| (a ^ b).count_ones()).sum();
+            errors as f64 / (length * (BANDS - 1)) as f64
+        };
+        (0..=MAX_SHIFT)
+            .flat_map(|shift| {
+                [
+                    compare(&self.frames[shift..], &other.frames),
+                    compare(&self.frames, &other.frames[shift..]),
+                ]
+            })
+            .fold(1.0, f64::min)
+    }
+
+    fn matches(&self, other: &Self) -> bool {
+        let frames = self.frames.len().abs_diff(other.frames.len());
+        frames as f64 <= DURATION_TOLERANCE * FRAMES_PER_SECOND as f64
+            && self.bit_error_rate(other) < MAX_BIT_ERROR_RATE
+    }
+}
+
+/// Fingerprint of decoded samples. Tag blocks (ID3, Vorbis comments, etc.) are not samples, so
+/// they do not affect it. Returns `None` for tracks which cannot be decoded or are too short.
+pub fn content_fingerprint(
+    fi: &FileInfo,
+    cancellation: &Cancellation,
+) -> io::Result<Option<Fingerprint>> {
+    let Some(mut track) = open(fi) else {
+        return Ok(None);
+    };
+    let Some(audio_track) = track.format.default_track() else {
+        return Ok(None);
+    };
+    let track_id = audio_track.id;
+    let Ok(mut decoder) = symphonia::default::get_codecs()
+        .make(&audio_track.codec_params, &DecoderOptions::default())
+    else {
+        return Ok(None);
+    };
+
+    let mut fingerprinter: Option<Fingerprinter> = None;
+    let mut decimation = 1;
+    let (mut sum, mut count) = (0.0, 0);
+    loop {
+        cancellation.check()?;
+        let packet = match track.format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(error)) if error.kind() == io::ErrorKind::UnexpectedEof => {
+                break
+            }
+            Err(_) => return Ok(None),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(_) => return Ok(None),
+        };
+        let spec = *decoded.spec();
+        let fingerprinter = fingerprinter.get_or_insert_with(|| {
+            decimation = (spec.rate / FINGERPRINT_RATE).max(1);
+            Fingerprinter::new(spec.rate / decimation)
+        });
+        let mut samples = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        samples.copy_interleaved_ref(decoded);
+
+        let channels = spec.channels.count().max(1);
+        for frame in samples.samples().chunks(channels) {
+            sum += frame.iter().map(|sample| *sample as f64).sum::<f64>() / channels as f64;
+            count += 1;
+            if count == decimation {
+                fingerprinter.push(sum / decimation as f64);
+                sum = 0.0;
+                count = 0;
+            }
+        }
+    }
+    let frames = fingerprinter.map(|f| f.frames).unwrap_or_default();
+    if frames.len() <= 2 * MAX_SHIFT {
+        return Ok(None);
+    }
+    Ok(Some(Fingerprint { frames }))
+}
+
+/// Artist, title and duration in seconds of a track.
+pub type Tags = (String, String, f64);
+
+fn tags(fi: &FileInfo) -> Option<Tags> {
+    let track = open(fi)?;
+    let params = &track.format.default_track()?.codec_params;
+    let duration = params
+        .time_base
+        .zip(params.n_frames)
+        .map(|(time_base, frames)| {
+            let time = time_base.calc_time(frames);
+            time.seconds as f64 + time.frac
+        })?;
+    Some((track.artist?, track.title?, duration))
+}
+
+fn largest_first(mut files: Vec<FileInfo>) -> Vec<FileInfo> {
+    files.sort_by_key(|fi| std::cmp::Reverse(fi.size));
+    files
+}
+
+/// Groups audio files which sound the same, i.e. with close fingerprints of decoded content.
+/// Every file is compared with the first (largest) file of a group. Files which cannot be
+/// decoded are ignored. Fingerprints are taken from `fingerprints` if the file did not change.
+pub fn group_by_content(
+    files: Vec<FileInfo>,
+    fingerprints: &mut ContentCache<Option<Fingerprint>>,
+    cancellation: &Cancellation,
+) -> io::Result<Vec<Vec<FileInfo>>> {
+    let mut groups: Vec<(Fingerprint, Vec<FileInfo>)> = Vec::new();
+    for fi in largest_first(files) {
+        let fingerprint =
+            fingerprints.get_or_compute(&fi, |fi| content_fingerprint(fi, cancellation))?;
+        let Some(fingerprint) = fingerprint else {
+            continue;
+        };
+        match groups
+            .iter_mut()
+            .find(|(first, _)| first.matches(&fingerprint))
+        {
+            Some((_, group)) => group.push(fi),
+            None => groups.push((fingerprint, vec![fi])),
+        }
+    }
+    Ok(groups
+        .into_iter()
+        .map(|(_, group)| group)
+        .filter(|group| group.len() >= 2)
+        .collect())
+}
+
+/// Groups audio files with the same artist and title and durations close to the shortest one
+/// of a group. Files without tags are ignored. Tags are taken from `cache` if the file did not
+/// change.
+pub fn group_by_tags(
+    files: Vec<FileInfo>,
+    cache: &mut ContentCache<Option<Tags>>,
+    cancellation: &Cancellation,
+) -> io::Result<Vec<Vec<FileInfo>>> {
+    let mut by_tags: HashMap<(String, String), Vec<(f64, FileInfo)>> = HashMap::new();
+    for fi in files {
+        cancellation.check()?;
+        if let Some((artist, title, duration)) = cache.get_or_compute(&fi, |fi| Ok(tags(fi)))? {
+            by_tags
+                .entry((artist, title))
+                .or_default()
+                .push((duration, fi));
+        }
+    }
+
+    let mut groups = Vec::new();
+    for (_, mut tracks) in by_tags {
+        tracks.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut group: Vec<FileInfo> = Vec::new();
+        let mut first_duration = f64::NEG_INFINITY;
+        for (duration, fi) in tracks {
+            if duration - first_duration > DURATION_TOLERANCE {
+                if !group.is_empty() {
+                    groups.push(std::mem::take(&mut group));
+                }
+                first_duration = duration;
+            }
+            group.push(fi);
+        }
+        groups.push(group);
+    }
+    Ok(groups
+        .into_iter()
+        .filter(|group| group.len() >= 2)
+        .map(largest_first)
+        .collect())
+}
@@ -0,0 +1,35 @@
+use crate::find_duplicates::{duplication_status, find_duplicate_groups, Cancellation};
+use crate::profiles;
+use std::error::Error;
+use std::io::{self, Write};
+
+/// Searches with options of a saved profile and prints groups of duplicates to the standard
+/// output, one path per line and an empty line after each group. The summary goes to stderr.
+pub fn run_profile(name: &str) -> Result<(), Box<dyn Error>> {
+    let options = profiles::load(name)?;
+    if options.paths.is_empty() {
+        return Err(format!("Profile \"{}\" has no search paths.", name).into());
+    }
+    let results = find_duplicate_groups(&options, &Cancellation::default())?;
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for group in &results.directories {
+        for dir in &group.directories {
+            writeln!(out, "{}", dir.path.display())?;
+        }
+        writeln!(out)?;
+    }
+    for group in results.uncollapsed_groups() {
+        for fi in group.files.iter().chain(&group.links) {
+            writeln!(out, "{}", fi.path.display())?;
+            for path in &fi.hard_links {
+                writeln!(out, "{}", path.display())?;
+            }
+        }
+        writeln!(out)?;
+    }
+
+    eprintln!("{}", duplication_status(&results));
+    Ok(())
+}
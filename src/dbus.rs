@@ -0,0 +1,129 @@
+use crate::gtk_prelude::*;
+use crate::main_window::MainWindow;
+use std::path::PathBuf;
+
+const INTERFACE: &str = "net.andy128k.FindDuplicates.Search";
+
+const INTERFACE_XML: &str = r#"
+<node>
+  <interface name="net.andy128k.FindDuplicates.Search">
+    <method name="AddPaths">
+      <arg type="as" name="paths" direction="in"/>
+    </method>
+    <method name="StartScan"/>
+    <method name="CancelScan"/>
+    <method name="GetStatus">
+      <arg type="s" name="status" direction="out"/>
+    </method>
+    <method name="GetResults">
+      <arg type="aas" name="groups" direction="out"/>
+    </method>
+  </interface>
+</node>
+"#;
+
+/// Window which receives calls, `None` if no window is open.
+fn existing_window(app: &gtk::Application) -> Option<MainWindow> {
+    app.active_window()
+        .into_iter()
+        .chain(app.windows())
+        .find_map(|window| window.downcast::<MainWindow>().ok())
+}
+
+/// Opens a window for calls which start a search when there is none.
+fn open_window(app: &gtk::Application) -> MainWindow {
+    let window = MainWindow::new(app);
+    window.show();
+    window
+}
+
+fn to_strings(paths: &[PathBuf]) -> Vec<String> {
+    paths
+        .iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect()
+}
+
+fn method_call(method: &str, parameters: glib::Variant, invocation: gio::DBusMethodInvocation) {
+    let Some(app) =
+        gio::Application::default().and_then(|app| app.downcast::<gtk::Application>().ok())
+    else {
+        invocation.return_dbus_error(
+            "org.freedesktop.DBus.Error.Failed",
+            "Application is not running.",
+        );
+        return;
+    };
+    // queries and cancellation do not open a window, without one nothing is searched
+    let window = existing_window(&app);
+    match method {
+        "AddPaths" => match parameters.get::<(Vec<String>,)>() {
+            Some((paths,)) => {
+                let paths: Vec<PathBuf> = paths.into_iter().map(PathBuf::from).collect();
+                let window = window.unwrap_or_else(|| open_window(&app));
+                window.add_directories(&paths);
+                window.present();
+                invocation.return_value(None);
+            }
+            None => invocation.return_dbus_error(
+                "org.freedesktop.DBus.Error.InvalidArgs",
+                "Array of paths is expected.",
+            ),
+        },
+        "StartScan" => {
+            window.unwrap_or_else(|| open_window(&app)).start_search();
+            invocation.return_value(None);
+        }
+        "CancelScan" => {
+            if let Some(window) = window {
+                window.cancel_search();
+            }
+            invocation.return_value(None);
+        }
+        "GetStatus" => {
+            let status = window.map(|window| window.status()).unwrap_or_default();
+            invocation.return_value(Some(&(status,).to_variant()));
+        }
+        "GetResults" => {
+            let groups: Vec<Vec<String>> = window
+                .map(|window| window.result_groups())
+                .unwrap_or_default()
+                .iter()
+                .map(|group| to_strings(group))
+                .collect();
+            invocation.return_value(Some(&(groups,).to_variant()));
+        }
+        _ => invocation.return_dbus_error(
+            "org.freedesktop.DBus.Error.UnknownMethod",
+            &format!("Unknown method {}.", method),
+        ),
+    }
+}
+
+/// Exports the scripting interface on the object path of the application. Calls are served by
+/// the main loop, so they are handled like actions of a window.
+pub fn register(app: &gtk::Application) {
+    let (Some(connection), Some(object_path)) = (app.dbus_connection(), app.dbus_object_path())
+    else {
+        return;
+    };
+    let interface = gio::DBusNodeInfo::for_xml(INTERFACE_XML)
+        .ok()
+        .and_then(|node| node.lookup_interface(INTERFACE));
+    let Some(interface) = interface else {
+        eprintln!("Cannot parse D-Bus interface {}.", INTERFACE);
+        return;
+    };
+    let result = connection.register_object(
+        &object_path,
+        &interface,
+        |_connection, _sender, _path, _interface, method, parameters, invocation| {
+            method_call(method, parameters, invocation)
+        },
+        |_connection, _sender, _path, _interface, _property| ().to_variant(),
+        |_connection, _sender, _path, _interface, _property, _value| false,
+    );
+    if let Err(error) = result {
+        eprintln!("Cannot register D-Bus interface {}. {}", INTERFACE, error);
+    }
+}
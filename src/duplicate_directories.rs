@@ -0,0 +1,283 @@
+use crate::find_duplicates::{DuplicatesGroup, MatchKind, Waste};
+use sha2::{
+    digest::{generic_array::GenericArray, OutputSizeUser},
+    Digest, Sha256,
+};
+use std::cmp::{min, Reverse};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Directories with less files are not reported, their files are shown as regular duplicates.
+const MIN_FILES: usize = 2;
+
+type Fingerprint = GenericArray<u8, <Sha256 as OutputSizeUser>::OutputSize>;
+
+#[derive(Debug)]
+pub struct DirectoryInfo {
+    pub path: PathBuf,
+    pub modified: SystemTime,
+    pub size: u64,
+    pub disk_usage: u64,
+    pub files: usize,
+}
+
+#[derive(Debug)]
+pub struct DirectoryGroup {
+    pub directories: Vec<DirectoryInfo>,
+    /// Similarity of directories in percents. Identical directories have 100.
+    pub similarity: u8,
+    /// Size of content found in each of the directories.
+    pub shared: u64,
+}
+
+impl DirectoryGroup {
+    pub fn size(&self) -> u64 {
+        self.directories
+            .iter()
+            .map(|dir| dir.size)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Space taken by shared content of all directories but the largest one. Content which
+    /// only some of the directories have is not a waste.
+    pub fn waste(&self) -> Waste {
+        let space = |dir: &DirectoryInfo| {
+            let shared = self.shared.min(dir.size);
+            Waste {
+                logical: shared,
+                on_disk: if dir.size == 0 {
+                    0
+                } else {
+                    (dir.disk_usage as u128 * shared as u128 / dir.size as u128) as u64
+                },
+            }
+        };
+        let total: Waste = self.directories.iter().map(space).sum();
+        let kept = self
+            .directories
+            .iter()
+            .max_by_key(|dir| dir.size)
+            .map(space)
+            .unwrap_or_default();
+        total - kept
+    }
+
+    /// Checks if all files of the group are inside of these directories, and copies of them are
+    /// in more than one of the directories. Files copied within one directory are duplicates
+    /// of their own.
+    pub fn covers(&self, group: &DuplicatesGroup) -> bool {
+        let mut holders = Vec::new();
+        for fi in &group.files {
+            let holder = self
+                .directories
+                .iter()
+                .position(|dir| fi.path.starts_with(&dir.path));
+            match holder {
+                Some(holder) if !holders.contains(&holder) => holders.push(holder),
+                Some(_) => {}
+                None => return false,
+            }
+        }
+        holders.len() > 1
+    }
+}
+
+#[derive(Default)]
+struct Subtree {
+    size: u64,
+    disk_usage: u64,
+    files: usize,
+    /// Number of files per duplicates group.
+    contents: HashMap<usize, usize>,
+    /// Subtree contains files which have no duplicates.
+    has_unique: bool,
+}
+
+impl Subtree {
+    /// Fingerprint of subtree's content. It is built from content of files only, so names and
+    /// layout of files do not matter. Files of the same group have the same hash, so group
+    /// index stands for it. Subtrees with unique files cannot be identical to anything.
+    fn fingerprint(&self) -> Option<Fingerprint> {
+        if self.has_unique {
+            return None;
+        }
+        let mut contents: Vec<(usize, usize)> =
+            self.contents.iter().map(|(g, c)| (*g, *c)).collect();
+        contents.sort_unstable();
+
+        let mut hasher = Sha256::new();
+        for (group, count) in contents {
+            hasher.update(group.to_le_bytes());
+            hasher.update(count.to_le_bytes());
+        }
+        Some(hasher.finalize())
+    }
+}
+
+fn collect_subtrees(
+    roots: &[PathBuf],
+    files: &[(PathBuf, Waste)],
+    group_of: &HashMap<&Path, usize>,
+) -> HashMap<PathBuf, Subtree> {
+    let mut subtrees: HashMap<PathBuf, Subtree> = HashMap::new();
+    for (path, space) in files {
+        let group = group_of.get(path.as_path());
+        for dir in path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| roots.iter().any(|root| dir.starts_with(root)))
+        {
+            let subtree = subtrees.entry(dir.to_path_buf()).or_default();
+            subtree.size += space.logical;
+            subtree.disk_usage += space.on_disk;
+            subtree.files += 1;
+            match group {
+                Some(group) => *subtree.contents.entry(*group).or_default() += 1,
+                None => subtree.has_unique = true,
+            }
+        }
+    }
+    subtrees
+}
+
+fn similarity(shared: u64, total: u64) -> u8 {
+    if total == 0 {
+        0
+    } else {
+        (200 * shared as u128 / total as u128) as u8
+    }
+}
+
+/// Leaves only directories which are not nested into other directories of the list.
+fn outermost(subtrees: &[(PathBuf, Subtree)], mut members: Vec<usize>) -> Vec<usize> {
+    members.sort_by_key(|m| subtrees[*m].0.components().count());
+    let mut result: Vec<usize> = Vec::new();
+    for member in members {
+        if !result
+            .iter()
+            .any(|r| subtrees[member].0.starts_with(&subtrees[*r].0))
+        {
+            result.push(member);
+        }
+    }
+    result
+}
+
+fn directory_info(path: &Path, subtree: &Subtree) -> DirectoryInfo {
+    let modified = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    DirectoryInfo {
+        path: path.to_path_buf(),
+        modified,
+        size: subtree.size,
+        disk_usage: subtree.disk_usage,
+        files: subtree.files,
+    }
+}
+
+pub fn find_duplicate_directories(
+    roots: &[PathBuf],
+    files: &[(PathBuf, Waste)],
+    groups: &[DuplicatesGroup],
+    min_similarity: u8,
+) -> Vec<DirectoryGroup> {
+    let mut group_of: HashMap<&Path, usize> = HashMap::new();
+    for (index, group) in groups.iter().enumerate() {
+        if group.kind != MatchKind::Exact {
+            continue;
+        }
+        for fi in &group.files {
+            group_of.insert(fi.path.as_path(), index);
+        }
+    }
+
+    let subtrees: Vec<(PathBuf, Subtree)> = collect_subtrees(roots, files, &group_of)
+        .into_iter()
+        .filter(|(_, subtree)| subtree.files >= MIN_FILES && !subtree.contents.is_empty())
+        .collect();
+    let fingerprints: Vec<Option<Fingerprint>> = subtrees
+        .iter()
+        .map(|(_, subtree)| subtree.fingerprint())
+        .collect();
+
+    let mut candidates: Vec<(u8, u64, Vec<usize>)> = Vec::new();
+
+    let mut by_fingerprint: HashMap<&Fingerprint, Vec<usize>> = HashMap::new();
+    for (index, fingerprint) in fingerprints.iter().enumerate() {
+        if let Some(fingerprint) = fingerprint {
+            by_fingerprint.entry(fingerprint).or_default().push(index);
+        }
+    }
+    for (_, members) in by_fingerprint {
+        let members = outermost(&subtrees, members);
+        if members.len() >= 2 {
+            let shared = subtrees[members[0]].1.size;
+            candidates.push((100, shared, members));
+        }
+    }
+
+    if min_similarity < 100 {
+        let mut index: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (dir, (_, subtree)) in subtrees.iter().enumerate() {
+            for group in subtree.contents.keys() {
+                index.entry(*group).or_default().push(dir);
+            }
+        }
+
+        for (a, (path_a, subtree_a)) in subtrees.iter().enumerate() {
+            let mut shared: HashMap<usize, u64> = HashMap::new();
+            for (group, count_a) in &subtree_a.contents {
+                for b in index[group].iter().filter(|b| **b > a) {
+                    let count_b = subtrees[*b].1.contents[group];
+                    *shared.entry(*b).or_default() +=
+                        min(*count_a, count_b) as u64 * groups[*group].size();
+                }
+            }
+
+            for (b, shared_bytes) in shared {
+                let (path_b, subtree_b) = &subtrees[b];
+                if path_a.starts_with(path_b) || path_b.starts_with(path_a) {
+                    continue;
+                }
+                if fingerprints[a].is_some() && fingerprints[a] == fingerprints[b] {
+                    continue;
+                }
+                let similarity = similarity(shared_bytes, subtree_a.size + subtree_b.size).min(99);
+                if similarity >= min_similarity {
+                    candidates.push((similarity, shared_bytes, vec![a, b]));
+                }
+            }
+        }
+    }
+
+    candidates.sort_by_key(|(similarity, _, members)| {
+        let size = members.iter().map(|m| subtrees[*m].1.size).max();
+        Reverse((size, *similarity))
+    });
+
+    // Nested duplicates are collapsed into the outer ones.
+    let mut reported: Vec<&Path> = Vec::new();
+    let mut result = Vec::new();
+    for (similarity, shared, members) in candidates {
+        let covered = members
+            .iter()
+            .all(|m| reported.iter().any(|dir| subtrees[*m].0.starts_with(dir)));
+        if covered {
+            continue;
+        }
+        reported.extend(members.iter().map(|m| subtrees[*m].0.as_path()));
+        result.push(DirectoryGroup {
+            directories: members
+                .iter()
+                .map(|m| directory_info(&subtrees[*m].0, &subtrees[*m].1))
+                .collect(),
+            similarity,
+            shared,
+        });
+    }
+    result
+}
@@ -1,265 +1,1058 @@
+use crate::find_duplicates::Waste;
 use crate::gtk_prelude::*;
-use crate::utils::scrolled;
+use crate::utils::{scrolled, BitsetExt};
 use chrono::prelude::*;
 use gtk::gdk::ffi::GDK_BUTTON_SECONDARY;
-use std::iter::Peekable;
+use gtk::gdk_pixbuf::Pixbuf;
+use gtk::subclass::prelude::*;
+use std::cell::RefCell;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Bound;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::time::SystemTime;
 
+const THUMBNAIL_SIZE: i32 = 64;
+
+mod imp {
+    use super::*;
+    use gtk::glib::once_cell::sync::OnceCell;
+    use std::cell::Cell;
+
+    #[derive(Default)]
+    pub struct DuplicateGroup {
+        pub label: RefCell<String>,
+        pub wasted: Cell<Waste>,
+        pub total: Cell<u64>,
+        /// Content of files may have changed since they were compared.
+        pub changed: Cell<bool>,
+        /// Similarity of directories of the group in percents.
+        pub similarity: Cell<u8>,
+        pub files: OnceCell<gio::ListStore>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for DuplicateGroup {
+        const NAME: &'static str = "DuplicateGroup";
+        type Type = super::DuplicateGroup;
+    }
+
+    impl ObjectImpl for DuplicateGroup {}
+
+    pub struct DuplicateFile {
+        pub path: RefCell<PathBuf>,
+        pub modified: Cell<SystemTime>,
+        pub size: Cell<u64>,
+        /// Number of files inside. Set for directories only.
+        pub directory_files: Cell<Option<usize>>,
+        /// Set for read-only members of archives.
+        pub archive: RefCell<Option<PathBuf>>,
+        pub link_target: RefCell<Option<PathBuf>>,
+        /// Number of hard links, including ones which have not been found.
+        pub nlink: Cell<u64>,
+        /// Other paths of the file in results.
+        pub hard_links: RefCell<Vec<PathBuf>>,
+        /// A thumbnail is shown for images. It is loaded when the row is shown first.
+        pub image: Cell<bool>,
+        pub loading: Cell<bool>,
+        pub thumbnail: RefCell<Option<Pixbuf>>,
+        pub group: glib::WeakRef<super::DuplicateGroup>,
+    }
+
+    impl Default for DuplicateFile {
+        fn default() -> Self {
+            Self {
+                path: Default::default(),
+                modified: Cell::new(SystemTime::UNIX_EPOCH),
+                size: Default::default(),
+                directory_files: Default::default(),
+                archive: Default::default(),
+                link_target: Default::default(),
+                nlink: Cell::new(1),
+                hard_links: Default::default(),
+                image: Default::default(),
+                loading: Default::default(),
+                thumbnail: Default::default(),
+                group: Default::default(),
+            }
+        }
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for DuplicateFile {
+        const NAME: &'static str = "DuplicateFile";
+        type Type = super::DuplicateFile;
+    }
+
+    impl ObjectImpl for DuplicateFile {}
+
+    #[derive(Default)]
+    pub struct FileSelection {
+        pub model: OnceCell<gio::ListModel>,
+        pub store: OnceCell<super::DuplicatesStore>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for FileSelection {
+        const NAME: &'static str = "DuplicatesFileSelection";
+        type Type = super::FileSelection;
+        type Interfaces = (gio::ListModel, gtk::SelectionModel);
+    }
+
+    impl ObjectImpl for FileSelection {}
+
+    impl ListModelImpl for FileSelection {
+        fn item_type(&self) -> glib::Type {
+            gtk::TreeListRow::static_type()
+        }
+
+        fn n_items(&self) -> u32 {
+            self.model.get().map_or(0, |model| model.n_items())
+        }
+
+        fn item(&self, position: u32) -> Option<glib::Object> {
+            self.model.get()?.item(position)
+        }
+    }
+
+    impl SelectionModelImpl for FileSelection {
+        fn is_selected(&self, position: u32) -> bool {
+            let store = self.store.get();
+            self.obj()
+                .file_at(position)
+                .is_some_and(|file| store.is_some_and(|store| store.is_selected(&file)))
+        }
+
+        fn selection_in_range(&self, position: u32, n_items: u32) -> gtk::Bitset {
+            let selection = gtk::Bitset::new_empty();
+            for position in position..position + n_items {
+                if self.is_selected(position) {
+                    selection.add(position);
+                }
+            }
+            selection
+        }
+
+        fn set_selection(&self, selected: &gtk::Bitset, mask: &gtk::Bitset) -> bool {
+            self.obj().set_selection(selected, mask);
+            true
+        }
+    }
+}
+
+glib::wrapper! {
+    /// Header of a group of duplicates. Files of the group are its children in the list.
+    pub struct DuplicateGroup(ObjectSubclass<imp::DuplicateGroup>);
+}
+
+glib::wrapper! {
+    /// File or directory of a group of duplicates.
+    pub struct DuplicateFile(ObjectSubclass<imp::DuplicateFile>);
+}
+
+glib::wrapper! {
+    /// Selection of rows which is kept by the store, so files of collapsed groups stay
+    /// selected. Group headers cannot be selected.
+    pub struct FileSelection(ObjectSubclass<imp::FileSelection>)
+        @implements gio::ListModel, gtk::SelectionModel;
+}
+
+fn items<T: IsA<glib::Object>>(model: &impl IsA<gio::ListModel>) -> Vec<T> {
+    (0..model.n_items())
+        .filter_map(|position| model.item(position).and_downcast::<T>())
+        .collect()
+}
+
+/// Position of the item in the model. Recently appended items are found first. It is meant for
+/// files of a group, groups are found by `DuplicatesStore::group_position`.
+fn position_of(model: &impl IsA<gio::ListModel>, item: &impl IsA<glib::Object>) -> Option<u32> {
+    let item = item.upcast_ref::<glib::Object>();
+    (0..model.n_items())
+        .rev()
+        .find(|position| model.item(*position).as_ref() == Some(item))
+}
+
+impl DuplicateGroup {
+    fn new(label: &str, wasted: Waste, total: u64) -> Self {
+        let group: Self = glib::Object::builder().build();
+        let private = group.imp();
+        *private.label.borrow_mut() = label.to_owned();
+        private.wasted.set(wasted);
+        private.total.set(total);
+        private
+            .files
+            .set(gio::ListStore::new(DuplicateFile::static_type()))
+            .unwrap();
+        group
+    }
+
+    fn files_model(&self) -> &gio::ListStore {
+        self.imp().files.get().unwrap()
+    }
+
+    pub fn files(&self) -> Vec<DuplicateFile> {
+        items(self.files_model())
+    }
+
+    fn add(&self, file: &DuplicateFile) {
+        file.imp().group.set(Some(self));
+        self.files_model().append(file);
+    }
+
+    fn name(&self) -> String {
+        let label = self.imp().label.borrow();
+        if self.imp().changed.get() {
+            format!("{} (changed, refresh to verify)", label)
+        } else {
+            label.clone()
+        }
+    }
+
+    fn details(&self) -> String {
+        let wasted = self.imp().wasted.get();
+        format!("{} wasted, {} on disk", wasted.logical, wasted.on_disk)
+    }
+
+    /// Takes the header of `other`, a group from newer results. Returns if the header has
+    /// changed.
+    fn assign(&self, other: &DuplicateGroup) -> bool {
+        let (private, other) = (self.imp(), other.imp());
+        let mut changed =
+            private.label.replace(other.label.borrow().clone()) != *other.label.borrow();
+        changed |= private.wasted.replace(other.wasted.get()) != other.wasted.get();
+        changed |= private.total.replace(other.total.get()) != other.total.get();
+        changed |= private.similarity.replace(other.similarity.get()) != other.similarity.get();
+        changed |= private.changed.replace(false);
+        changed
+    }
+}
+
+impl DuplicateFile {
+    fn new(path: &Path, modified: SystemTime, size: u64) -> Self {
+        let file: Self = glib::Object::builder().build();
+        let private = file.imp();
+        *private.path.borrow_mut() = path.to_path_buf();
+        private.modified.set(modified);
+        private.size.set(size);
+        file
+    }
+
+    fn group(&self) -> Option<DuplicateGroup> {
+        self.imp().group.upgrade()
+    }
+
+    fn name(&self) -> String {
+        let private = self.imp();
+        let path = private.path.borrow();
+        let mut name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if let Some(target) = &*private.link_target.borrow() {
+            name = format!("{} → {}", name, target.display());
+        }
+        if private.nlink.get() > 1 {
+            name = format!("{} ({} links)", name, private.nlink.get());
+        }
+        name
+    }
+
+    fn directory(&self) -> String {
+        let path = self.imp().path.borrow();
+        path.parent()
+            .map(|dir| dir.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    fn date(&self) -> String {
+        let date: DateTime<Local> = self.imp().modified.get().into();
+        if Local::now().signed_duration_since(date) > chrono::Duration::days(182) {
+            date.format("%b %e %Y")
+        } else {
+            date.format("%b %e")
+        }
+        .to_string()
+    }
+
+    /// Takes what is shown of `other`, a row of the same path from newer results. Returns if
+    /// the row has changed.
+    fn assign(&self, other: &DuplicateFile) -> bool {
+        let (private, other) = (self.imp(), other.imp());
+        let mut changed = private.modified.replace(other.modified.get()) != other.modified.get();
+        changed |= private.size.replace(other.size.get()) != other.size.get();
+        if changed {
+            private.thumbnail.replace(None);
+        }
+        changed |= private.directory_files.replace(other.directory_files.get())
+            != other.directory_files.get();
+        changed |=
+            private.archive.replace(other.archive.borrow().clone()) != *other.archive.borrow();
+        changed |= private
+            .link_target
+            .replace(other.link_target.borrow().clone())
+            != *other.link_target.borrow();
+        changed |= private.nlink.replace(other.nlink.get()) != other.nlink.get();
+        changed |= private
+            .hard_links
+            .replace(other.hard_links.borrow().clone())
+            != *other.hard_links.borrow();
+        changed |= private.image.replace(other.image.get()) != other.image.get();
+        changed
+    }
+
+    fn tooltip(&self) -> Option<String> {
+        let private = self.imp();
+        if private.nlink.get() <= 1 {
+            return None;
+        }
+        let mut tooltip = format!(
+            "{} hard links:\n{}",
+            private.nlink.get(),
+            private.path.borrow().display()
+        );
+        for other in private.hard_links.borrow().iter() {
+            tooltip.push('\n');
+            tooltip.push_str(&other.to_string_lossy());
+        }
+        Some(tooltip)
+    }
+}
+
+/// Item of a row of the list.
+enum Row<'i> {
+    Group(&'i DuplicateGroup),
+    File(&'i DuplicateFile),
+}
+
+impl<'i> Row<'i> {
+    fn of(item: &'i glib::Object) -> Option<Self> {
+        if let Some(group) = item.downcast_ref::<DuplicateGroup>() {
+            Some(Self::Group(group))
+        } else {
+            item.downcast_ref::<DuplicateFile>().map(Self::File)
+        }
+    }
+}
+
+type Predicate = Box<dyn Fn(&Path) -> bool>;
+
+fn is_shown(predicate: &Option<Predicate>, group: &DuplicateGroup) -> bool {
+    let Some(predicate) = predicate else {
+        return true;
+    };
+    group
+        .files()
+        .iter()
+        .any(|file| predicate(&file.imp().path.borrow()))
+}
+
+/// Paths the file is found by in the index of the store.
+fn indexed_paths(file: &DuplicateFile) -> Vec<PathBuf> {
+    let private = file.imp();
+    let mut paths = vec![private.path.borrow().clone()];
+    paths.extend(private.archive.borrow().clone());
+    paths
+}
+
 #[derive(Clone)]
-pub struct DuplicatesStore(gtk::ListStore);
-
-enum StoreColumn {
-    IsGroup = 0,
-    Name = 1,
-    Directory = 2,
-    Time = 3,
-    Size = 4,
-
-    Path = 5,
-    Modified = 6,
-    Background = 7,
+pub struct DuplicatesStore {
+    groups: gio::ListStore,
+    filter: gtk::CustomFilter,
+    predicate: Rc<RefCell<Option<Predicate>>>,
+    model: gtk::FilterListModel,
+    selected: Rc<RefCell<HashSet<DuplicateFile>>>,
+    /// Files by path and members of archives also by path of the archive, for `find_under`.
+    index: Rc<RefCell<BTreeMap<PathBuf, Vec<DuplicateFile>>>>,
+    /// Positions of groups in `groups`. Built on a lookup and dropped when groups are added or
+    /// removed.
+    positions: Rc<RefCell<Option<HashMap<DuplicateGroup, u32>>>>,
 }
 
 impl Default for DuplicatesStore {
     fn default() -> Self {
-        Self(gtk::ListStore::new(&[
-            glib::Type::BOOL,   // IsGroup
-            glib::Type::STRING, // Name
-            glib::Type::STRING, // Directory
-            glib::Type::STRING, // Time
-            glib::Type::STRING, // Size
-            glib::Type::STRING, // Path
-            glib::Type::STRING, // Modified
-            glib::Type::STRING, // Background
-        ]))
+        let groups = gio::ListStore::new(DuplicateGroup::static_type());
+        let predicate: Rc<RefCell<Option<Predicate>>> = Default::default();
+        let filter = gtk::CustomFilter::new(clone!(@strong predicate => move |item| {
+            item.downcast_ref::<DuplicateGroup>()
+                .is_none_or(|group| is_shown(&predicate.borrow(), group))
+        }));
+        let model = gtk::FilterListModel::new(Some(&groups), Some(&filter));
+        let positions: Rc<RefCell<Option<HashMap<DuplicateGroup, u32>>>> = Default::default();
+        // rows which are only redrawn keep their positions
+        groups.connect_items_changed(
+            clone!(@strong positions => move |_groups, _position, removed, added| {
+                if removed != added {
+                    positions.borrow_mut().take();
+                }
+            }),
+        );
+        Self {
+            groups,
+            filter,
+            predicate,
+            model,
+            selected: Default::default(),
+            index: Default::default(),
+            positions,
+        }
     }
 }
 
 impl DuplicatesStore {
     pub fn is_empty(&self) -> bool {
-        self.0.iter_first().is_none()
+        self.groups.n_items() == 0
     }
 
-    pub fn append_group(&self, group_size: usize, file_size: u64) {
-        let iter = self.0.append();
-        self.0.set_value(
-            &iter,
-            StoreColumn::IsGroup as u32,
-            &glib::Value::from(&true),
-        );
-        self.0.set_value(
-            &iter,
-            StoreColumn::Name as u32,
-            &glib::Value::from(&format!("{} x {}", group_size, file_size)),
-        );
-        self.0.set_value(
-            &iter,
-            StoreColumn::Directory as u32,
-            &glib::Value::from(&format!("{} wasted", (group_size - 1) as u64 * file_size)),
-        );
-        self.0
-            .set_value(&iter, StoreColumn::Time as u32, &glib::Value::from(""));
-        self.0.set_value(
-            &iter,
-            StoreColumn::Size as u32,
-            &glib::Value::from(&format!("{}", group_size as u64 * file_size)),
-        );
-        self.0.set_value(
-            &iter,
-            StoreColumn::Background as u32,
-            &glib::Value::from("#EEEEEE"),
-        );
+    fn append_header(&self, name: &str, wasted: Waste, total: u64) {
+        self.groups
+            .append(&DuplicateGroup::new(name, wasted, total));
     }
 
-    pub fn append_file(&self, path1: &Path, modified: SystemTime, file_size: u64) {
-        let iter = self.0.append();
-        self.0.set_value(
-            &iter,
-            StoreColumn::IsGroup as u32,
-            &glib::Value::from(&false),
+    pub fn append_group(&self, group_size: usize, file_size: u64, wasted: Waste) {
+        self.append_header(
+            &format!("{} x {}", group_size, file_size),
+            wasted,
+            group_size as u64 * file_size,
         );
-        self.set_path(&iter, path1);
-        let date: DateTime<Local> = modified.into();
-        let time_str = if Local::now().signed_duration_since(date) > chrono::Duration::days(182) {
-            date.format("%b %e %Y")
+    }
+
+    pub fn append_directory_group(
+        &self,
+        group_size: usize,
+        dir_size: u64,
+        similarity: u8,
+        wasted: Waste,
+    ) {
+        let name = if similarity >= 100 {
+            format!("{} directories x {}", group_size, dir_size)
         } else {
-            date.format("%b %e")
+            format!(
+                "{} directories x {} ({}% similar)",
+                group_size, dir_size, similarity
+            )
+        };
+        self.append_header(&name, wasted, group_size as u64 * dir_size);
+        let last = self.groups.n_items() - 1;
+        if let Some(group) = self.groups.item(last).and_downcast::<DuplicateGroup>() {
+            group.imp().similarity.set(similarity);
         }
-        .to_string();
-        self.0.set_value(
-            &iter,
-            StoreColumn::Time as u32,
-            &glib::Value::from(&time_str),
-        );
-        self.0.set_value(
-            &iter,
-            StoreColumn::Size as u32,
-            &glib::Value::from(&format!("{}", file_size)),
-        );
-        self.0.set_value(
-            &iter,
-            StoreColumn::Modified as u32,
-            &glib::Value::from(&date.to_rfc3339()),
-        );
     }
 
-    pub fn set_path(&self, iter: &gtk::TreeIter, path: &Path) {
-        self.0.set_value(
-            &iter,
-            StoreColumn::Name as u32,
-            &glib::Value::from(path.file_name().unwrap().to_str().unwrap()),
-        );
-        self.0.set_value(
-            &iter,
-            StoreColumn::Directory as u32,
-            &glib::Value::from(path.parent().unwrap().to_str().unwrap()),
-        );
-        self.0.set_value(
-            &iter,
-            StoreColumn::Path as u32,
-            &glib::Value::from(path.to_str().unwrap()),
-        );
+    pub fn append_matched_group(&self, label: &str, wasted: Waste, total: u64) {
+        self.append_header(label, wasted, total);
     }
 
-    pub fn get_fs_path(&self, iter: &gtk::TreeIter) -> Option<PathBuf> {
-        let path = self.0.get::<String>(iter, StoreColumn::Path as i32);
-        Some(Path::new(&path).to_path_buf())
+    /// Appends the file to the last group.
+    pub fn append_file(&self, path: &Path, modified: SystemTime, file_size: u64) -> DuplicateFile {
+        let file = DuplicateFile::new(path, modified, file_size);
+        let last = self.groups.n_items().checked_sub(1);
+        if let Some(group) = last.and_then(|position| self.groups.item(position)) {
+            group.downcast::<DuplicateGroup>().unwrap().add(&file);
+        }
+        self.add_to_index(&file);
+        file
     }
 
-    pub fn is_group(&self, iter: &gtk::TreeIter) -> bool {
-        self.0.get::<bool>(iter, StoreColumn::IsGroup as i32)
+    /// Appends the directory with `files` files inside to the last group.
+    pub fn append_directory(
+        &self,
+        path: &Path,
+        modified: SystemTime,
+        dir_size: u64,
+        files: usize,
+    ) -> DuplicateFile {
+        let file = self.append_file(path, modified, dir_size);
+        file.imp().directory_files.set(Some(files));
+        file
     }
 
-    pub fn modified(&self, iter: &gtk::TreeIter) -> DateTime<Local> {
-        let s = self.0.get::<String>(iter, StoreColumn::Modified as i32);
-        DateTime::parse_from_rfc3339(&s)
-            .unwrap()
-            .with_timezone(&Local)
+    pub fn append_image(&self, path: &Path, modified: SystemTime, file_size: u64) -> DuplicateFile {
+        let file = self.append_file(path, modified, file_size);
+        file.imp().image.set(true);
+        file
     }
 
-    pub fn to_model(&self) -> gtk::ListStore {
-        self.0.clone()
+    /// Redraws the row of the file.
+    fn file_changed(&self, file: &DuplicateFile) {
+        if let Some(group) = file.group() {
+            if let Some(position) = position_of(group.files_model(), file) {
+                group.files_model().items_changed(position, 1, 1);
+            }
+        }
     }
 
-    pub fn clear(&self) {
-        self.0.clear();
+    /// Marks the row as a read-only member of the archive.
+    pub fn set_archive(&self, file: &DuplicateFile, archive: &Path) {
+        self.remove_from_index(file);
+        *file.imp().archive.borrow_mut() = Some(archive.to_path_buf());
+        self.add_to_index(file);
+        self.file_changed(file);
+    }
+
+    /// Shows the row as a symbolic link pointing to `target`.
+    pub fn set_link_target(&self, file: &DuplicateFile, target: &Path) {
+        *file.imp().link_target.borrow_mut() = Some(target.to_path_buf());
+        self.file_changed(file);
+    }
+
+    /// Shows the row as a hard-linked file. `others` are other paths of the file in results,
+    /// `nlink` counts also links which have not been found.
+    pub fn set_hard_links(&self, file: &DuplicateFile, nlink: u64, others: &[PathBuf]) {
+        file.imp().nlink.set(nlink);
+        *file.imp().hard_links.borrow_mut() = others.to_vec();
+        self.file_changed(file);
+    }
+
+    /// Number of files inside of the directory of the row, `None` for rows of files.
+    pub fn get_directory_files(&self, file: &DuplicateFile) -> Option<usize> {
+        file.imp().directory_files.get()
+    }
+
+    /// Other paths of the file in the row.
+    pub fn get_hard_links(&self, file: &DuplicateFile) -> Vec<PathBuf> {
+        file.imp().hard_links.borrow().clone()
     }
 
-    pub fn iter(&self) -> DuplicatesStoreIter {
-        let iter = self.0.iter_first();
-        DuplicatesStoreIter {
-            store: self.clone(),
-            iter,
+    /// Files at `path` or inside of it, including members of archives there.
+    pub fn find_under(&self, path: &Path) -> Vec<DuplicateFile> {
+        let index = self.index.borrow();
+        let mut found = HashSet::new();
+        // paths under `path` follow it in the order of paths
+        index
+            .range::<Path, _>((Bound::Included(path), Bound::Unbounded))
+            .take_while(|(indexed, _)| indexed.starts_with(path))
+            .flat_map(|(_, files)| files)
+            .filter(|file| found.insert(*file))
+            .cloned()
+            .collect()
+    }
+
+    fn add_to_index(&self, file: &DuplicateFile) {
+        let mut index = self.index.borrow_mut();
+        for path in indexed_paths(file) {
+            index.entry(path).or_default().push(file.clone());
         }
     }
 
-    pub fn group_iter(&self) -> DuplicatesStoreGroupIter {
-        DuplicatesStoreGroupIter {
-            store: self.clone(),
-            iter: self.iter().peekable(),
+    fn remove_from_index(&self, file: &DuplicateFile) {
+        let mut index = self.index.borrow_mut();
+        for path in indexed_paths(file) {
+            if let Some(files) = index.get_mut(&path) {
+                files.retain(|indexed| indexed != file);
+                if files.is_empty() {
+                    index.remove(&path);
+                }
+            }
+        }
+    }
+
+    /// Marks the group of the file as one which content may have changed since it was
+    /// compared.
+    pub fn mark_changed(&self, file: &DuplicateFile) {
+        let Some(group) = file.group() else {
+            return;
+        };
+        if group.imp().changed.replace(true) {
+            return;
+        }
+        if let Some(position) = self.group_position(&group) {
+            self.groups.items_changed(position, 1, 1);
         }
     }
 
-    pub fn get_ref(&self, iter: &gtk::TreeIter) -> Option<gtk::TreeRowReference> {
-        let path = self.0.path(iter);
-        gtk::TreeRowReference::new(&self.0, &path)
+    fn group_position(&self, group: &DuplicateGroup) -> Option<u32> {
+        let mut positions = self.positions.borrow_mut();
+        positions
+            .get_or_insert_with(|| items(&self.groups).into_iter().zip(0..).collect())
+            .get(group)
+            .copied()
+    }
+
+    /// Shows only groups with a file for which `predicate` holds, and all files of them. `None`
+    /// shows all groups.
+    pub fn set_filter(&self, predicate: Option<Predicate>) {
+        *self.predicate.borrow_mut() = predicate;
+        self.filter.changed(gtk::FilterChange::Different);
+    }
+
+    pub fn get_archive(&self, file: &DuplicateFile) -> Option<PathBuf> {
+        file.imp().archive.borrow().clone()
+    }
+
+    pub fn is_read_only(&self, file: &DuplicateFile) -> bool {
+        file.imp().archive.borrow().is_some()
+    }
+
+    pub fn set_path(&self, file: &DuplicateFile, path: &Path) {
+        self.remove_from_index(file);
+        *file.imp().path.borrow_mut() = path.to_path_buf();
+        self.add_to_index(file);
+        self.file_changed(file);
     }
 
-    fn remove_iters(&self, iters: &[gtk::TreeIter]) {
-        let to_remove: Vec<gtk::TreeRowReference> =
-            iters.iter().filter_map(|iter| self.get_ref(iter)).collect();
+    pub fn get_fs_path(&self, file: &DuplicateFile) -> Option<PathBuf> {
+        Some(file.imp().path.borrow().clone())
+    }
+
+    pub fn modified(&self, file: &DuplicateFile) -> DateTime<Local> {
+        file.imp().modified.get().into()
+    }
+
+    /// Groups as shown, i.e. without ones which are filtered out.
+    pub fn to_model(&self) -> gio::ListModel {
+        self.model.clone().upcast()
+    }
 
-        for row_ref in to_remove {
-            if let Some(path) = row_ref.path() {
-                if let Some(iter) = self.0.iter(&path) {
-                    self.0.remove(&iter);
+    pub fn clear(&self) {
+        self.selected.borrow_mut().clear();
+        self.index.borrow_mut().clear();
+        self.groups.remove_all();
+    }
+
+    pub fn is_selected(&self, file: &DuplicateFile) -> bool {
+        self.selected.borrow().contains(file)
+    }
+
+    fn select(&self, file: &DuplicateFile, selected: bool) {
+        if selected {
+            self.selected.borrow_mut().insert(file.clone());
+        } else {
+            self.selected.borrow_mut().remove(file);
+        }
+    }
+
+    fn unselect_all(&self) {
+        self.selected.borrow_mut().clear();
+    }
+
+    /// Selects or unselects files of shown groups, including collapsed ones.
+    fn update_selection(&self, mut selected: impl FnMut(&DuplicateFile, bool) -> bool) {
+        for group in items::<DuplicateGroup>(&self.model) {
+            for file in group.files() {
+                let is_selected = self.is_selected(&file);
+                if selected(&file, is_selected) != is_selected {
+                    self.select(&file, !is_selected);
                 }
             }
         }
     }
 
-    pub fn remove_all(&self, iters: &[gtk::TreeIter]) {
-        self.remove_iters(iters);
+    /// Selected files of shown groups, ordered by path.
+    fn selected_files(&self) -> Vec<DuplicateFile> {
+        let predicate = self.predicate.borrow();
+        let mut files: Vec<DuplicateFile> = self
+            .selected
+            .borrow()
+            .iter()
+            .filter(|file| {
+                file.group()
+                    .is_some_and(|group| is_shown(&predicate, &group))
+            })
+            .cloned()
+            .collect();
+        files.sort_by(|a, b| a.imp().path.borrow().cmp(&b.imp().path.borrow()));
+        files
+    }
+
+    /// Files of all groups.
+    pub fn iter(&self) -> impl Iterator<Item = DuplicateFile> {
+        self.group_iter().flat_map(|(_group, files)| files)
+    }
+
+    pub fn group_iter(&self) -> impl Iterator<Item = (DuplicateGroup, Vec<DuplicateFile>)> {
+        items::<DuplicateGroup>(&self.groups)
+            .into_iter()
+            .map(|group| {
+                let files = group.files();
+                (group, files)
+            })
+    }
+
+    /// Takes groups of `other`, newer results of the same search, keeping rows which are in
+    /// both, so they stay selected and expanded. Groups are matched by paths of their files:
+    /// files which are gone are removed, new ones are added and groups which are not in `other`
+    /// are removed.
+    pub fn update(&self, other: &DuplicatesStore) {
+        let groups: Vec<DuplicateGroup> = items(&self.groups);
+        let mut group_of: HashMap<PathBuf, usize> = HashMap::new();
+        for (index, group) in groups.iter().enumerate() {
+            for file in group.files() {
+                group_of.insert(file.imp().path.borrow().clone(), index);
+            }
+        }
 
-        let mut to_remove: Vec<gtk::TreeIter> = Vec::new();
-        for (group, files) in self.group_iter() {
-            if files.len() <= 1 {
-                to_remove.push(group);
-                to_remove.extend(files);
+        let mut newer: Vec<Option<DuplicateGroup>> = vec![None; groups.len()];
+        let mut added = Vec::new();
+        for group in items::<DuplicateGroup>(&other.groups) {
+            let index = group
+                .files()
+                .iter()
+                .filter_map(|file| group_of.get(&*file.imp().path.borrow()).copied())
+                .find(|index| newer[*index].is_none());
+            match index {
+                Some(index) => newer[index] = Some(group),
+                None => added.push(group),
             }
         }
 
-        self.remove_iters(&to_remove)
+        for (position, (group, newer)) in groups.iter().zip(newer).enumerate().rev() {
+            let position = position as u32;
+            match newer {
+                Some(newer) => {
+                    if self.update_group(group, &newer) {
+                        self.groups.items_changed(position, 1, 1);
+                    }
+                }
+                None => {
+                    for file in group.files() {
+                        self.select(&file, false);
+                    }
+                    self.groups.remove(position);
+                }
+            }
+        }
+        self.groups.extend_from_slice(&added);
+
+        self.index.borrow_mut().clear();
+        for file in self.iter() {
+            self.add_to_index(&file);
+        }
     }
-}
 
-pub struct DuplicatesStoreIter {
-    store: DuplicatesStore,
-    iter: Option<gtk::TreeIter>,
-}
+    /// Updates files and the header of the group from `newer`. Returns if the header has
+    /// changed.
+    fn update_group(&self, group: &DuplicateGroup, newer: &DuplicateGroup) -> bool {
+        let mut new_files: HashMap<PathBuf, DuplicateFile> = newer
+            .files()
+            .into_iter()
+            .map(|file| {
+                let path = file.imp().path.borrow().clone();
+                (path, file)
+            })
+            .collect();
+        let model = group.files_model();
+        for position in (0..model.n_items()).rev() {
+            let Some(file) = model.item(position).and_downcast::<DuplicateFile>() else {
+                continue;
+            };
+            let path = file.imp().path.borrow().clone();
+            match new_files.remove(&path) {
+                Some(new_file) => {
+                    if file.assign(&new_file) {
+                        model.items_changed(position, 1, 1);
+                    }
+                }
+                None => {
+                    self.select(&file, false);
+                    model.remove(position);
+                }
+            }
+        }
+        for file in newer.files() {
+            if new_files.contains_key(&*file.imp().path.borrow()) {
+                group.add(&file);
+            }
+        }
+        group.assign(newer)
+    }
 
-impl Iterator for DuplicatesStoreIter {
-    type Item = gtk::TreeIter;
+    /// Removes the files, and groups which are left with one file or none.
+    pub fn remove_all(&self, files: &[DuplicateFile]) {
+        let mut removed: HashMap<DuplicateGroup, HashSet<DuplicateFile>> = HashMap::new();
+        for file in files {
+            self.select(file, false);
+            self.remove_from_index(file);
+            if let Some(group) = file.group() {
+                removed.entry(group).or_default().insert(file.clone());
+            }
+        }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let current = self.iter.as_ref()?.clone();
-        let next = current.clone();
-        if self.store.0.iter_next(&next) {
-            self.iter = Some(next);
-        } else {
-            self.iter = None;
+        let mut emptied = Vec::new();
+        for (group, files) in removed {
+            let model = group.files_model();
+            for position in (0..model.n_items()).rev() {
+                let file = model.item(position).and_downcast::<DuplicateFile>();
+                if file.is_some_and(|file| files.contains(&file)) {
+                    model.remove(position);
+                }
+            }
+            if model.n_items() <= 1 {
+                if let Some(position) = self.group_position(&group) {
+                    emptied.push((position, group));
+                }
+            }
+        }
+
+        // later positions go first, so earlier ones stay valid
+        emptied.sort_unstable_by_key(|(position, _)| Reverse(*position));
+        for (position, group) in emptied {
+            for file in group.files() {
+                self.select(&file, false);
+                self.remove_from_index(&file);
+            }
+            self.groups.remove(position);
         }
-        Some(current)
     }
 }
 
-pub struct DuplicatesStoreGroupIter {
-    store: DuplicatesStore,
-    iter: Peekable<DuplicatesStoreIter>,
+fn row_item(list_item: &gtk::ListItem) -> Option<glib::Object> {
+    list_item.item().and_downcast::<gtk::TreeListRow>()?.item()
 }
 
-impl DuplicatesStoreGroupIter {
-    fn take_group(&mut self) -> Option<gtk::TreeIter> {
-        loop {
-            let iter = self.iter.next()?;
-            if self.store.is_group(&iter) {
-                return Some(iter);
+fn style_label(label: &gtk::Label, row: &Row) {
+    for class in ["heading", "warning", "dim-label"] {
+        label.remove_css_class(class);
+    }
+    match row {
+        Row::Group(group) => {
+            label.add_css_class("heading");
+            if group.imp().changed.get() {
+                label.add_css_class("warning");
+            }
+            label.set_tooltip_text(None);
+        }
+        Row::File(file) => {
+            if file.imp().archive.borrow().is_some() {
+                label.add_css_class("dim-label");
             }
+            label.set_tooltip_text(file.tooltip().as_deref());
         }
     }
+}
 
-    fn take_files(&mut self) -> Vec<gtk::TreeIter> {
-        let mut files = Vec::new();
-        loop {
-            if let Some(iter) = self.iter.peek() {
-                if self.store.is_group(iter) {
-                    break;
-                } else {
-                    files.push(self.iter.next().unwrap());
+/// Selects the row on a right click unless it is selected already, so the popup acts on it.
+fn select_on_secondary_click(widget: &gtk::Widget, list_item: &gtk::ListItem) {
+    let click = gtk::GestureClick::builder()
+        .button(GDK_BUTTON_SECONDARY as u32)
+        .build();
+    click.connect_pressed(
+        clone!(@weak widget, @weak list_item => move |_gesture, _n, _x, _y| {
+            if !list_item.is_selected() {
+                let target = (list_item.position(), false, false).to_variant();
+                if let Err(error) = widget.activate_action("list.select-item", Some(&target)) {
+                    eprintln!("Cannot select a row: {}", error);
                 }
-            } else {
-                break;
             }
+        }),
+    );
+    widget.add_controller(&click);
+}
+
+fn text_factory(text: impl Fn(&Row) -> String + 'static) -> gtk::ListItemFactory {
+    let factory = gtk::SignalListItemFactory::new();
+    factory.connect_setup(|_, list_item| {
+        let label = gtk::Label::builder()
+            .halign(gtk::Align::Start)
+            .ellipsize(pango::EllipsizeMode::End)
+            .build();
+        select_on_secondary_click(label.upcast_ref(), list_item);
+        list_item.set_child(Some(&label));
+    });
+    factory.connect_bind(move |_, list_item| {
+        let Some(label) = list_item.child().and_downcast::<gtk::Label>() else {
+            return;
+        };
+        let Some(item) = row_item(list_item) else {
+            return;
+        };
+        if let Some(row) = Row::of(&item) {
+            label.set_text(&text(&row));
+            style_label(&label, &row);
         }
-        files
+    });
+    factory.connect_teardown(|_, list_item| list_item.set_child(gtk::Widget::NONE));
+    factory.upcast()
+}
+
+/// Shows the thumbnail of the file. It is loaded and scaled in the background the first time,
+/// then it is shown if the list item still shows the file.
+fn show_thumbnail(image: &gtk::Image, file: &DuplicateFile, list_item: &gtk::ListItem) {
+    let private = file.imp();
+    let thumbnail = private.thumbnail.borrow().clone();
+    image.set_from_pixbuf(thumbnail.as_ref());
+    image.set_visible(true);
+    if thumbnail.is_some() || private.loading.replace(true) {
+        return;
     }
+    let path = private.path.borrow().clone();
+    glib::MainContext::default().spawn_local(
+        clone!(@weak file, @weak image, @weak list_item => async move {
+            let stream = gio::File::for_path(&path)
+                .read_future(glib::PRIORITY_LOW)
+                .await;
+            let thumbnail = match stream {
+                Ok(stream) => Pixbuf::from_stream_at_scale_future(
+                    &stream,
+                    THUMBNAIL_SIZE,
+                    THUMBNAIL_SIZE,
+                    true,
+                )
+                .await
+                .ok(),
+                Err(_) => None,
+            };
+            // the file is shown without a thumbnail if it cannot be loaded
+            file.imp().image.set(thumbnail.is_some());
+            *file.imp().thumbnail.borrow_mut() = thumbnail.clone();
+            if row_item(&list_item).as_ref() == Some(file.upcast_ref()) {
+                image.set_from_pixbuf(thumbnail.as_ref());
+                image.set_visible(thumbnail.is_some());
+            }
+        }),
+    );
 }
 
-impl Iterator for DuplicatesStoreGroupIter {
-    type Item = (gtk::TreeIter, Vec<gtk::TreeIter>);
+/// Name with an expander of groups and a thumbnail of images.
+fn name_factory() -> gtk::ListItemFactory {
+    let factory = gtk::SignalListItemFactory::new();
+    factory.connect_setup(|_, list_item| {
+        let thumbnail = gtk::Image::builder()
+            .pixel_size(THUMBNAIL_SIZE)
+            .visible(false)
+            .build();
+        let label = gtk::Label::builder()
+            .halign(gtk::Align::Start)
+            .ellipsize(pango::EllipsizeMode::End)
+            .build();
+        let row = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(6)
+            .build();
+        row.append(&thumbnail);
+        row.append(&label);
+        let expander = gtk::TreeExpander::builder().child(&row).build();
+        select_on_secondary_click(expander.upcast_ref(), list_item);
+        list_item.set_child(Some(&expander));
+    });
+    factory.connect_bind(|_, list_item| {
+        let Some(expander) = list_item.child().and_downcast::<gtk::TreeExpander>() else {
+            return;
+        };
+        let list_row = list_item.item().and_downcast::<gtk::TreeListRow>();
+        expander.set_list_row(list_row.as_ref());
+        let Some(item) = list_row.and_then(|list_row| list_row.item()) else {
+            return;
+        };
+        let Some(row) = Row::of(&item) else {
+            return;
+        };
+        let Some(content) = expander.child() else {
+            return;
+        };
+        if let Some(label) = content.last_child().and_downcast::<gtk::Label>() {
+            match &row {
+                Row::Group(group) => label.set_text(&group.name()),
+                Row::File(file) => label.set_text(&file.name()),
+            }
+            style_label(&label, &row);
+        }
+        if let Some(thumbnail) = content.first_child().and_downcast::<gtk::Image>() {
+            match &row {
+                Row::File(file) if file.imp().image.get() => {
+                    show_thumbnail(&thumbnail, file, list_item)
+                }
+                _ => {
+                    thumbnail.set_from_pixbuf(None);
+                    thumbnail.set_visible(false);
+                }
+            }
+        }
+    });
+    factory.connect_unbind(|_, list_item| {
+        if let Some(expander) = list_item.child().and_downcast::<gtk::TreeExpander>() {
+            expander.set_list_row(None);
+        }
+    });
+    factory.connect_teardown(|_, list_item| list_item.set_child(gtk::Widget::NONE));
+    factory.upcast()
+}
+
+/// Sorts groups and files of a group separately, so files stay within their groups.
+fn sorter(
+    groups: impl Fn(&DuplicateGroup, &DuplicateGroup) -> Ordering + 'static,
+    files: impl Fn(&DuplicateFile, &DuplicateFile) -> Ordering + 'static,
+) -> gtk::Sorter {
+    gtk::CustomSorter::new(move |a, b| {
+        match (Row::of(a), Row::of(b)) {
+            (Some(Row::Group(a)), Some(Row::Group(b))) => groups(a, b),
+            (Some(Row::File(a)), Some(Row::File(b))) => files(a, b),
+            _ => Ordering::Equal,
+        }
+        .into()
+    })
+    .upcast()
+}
+
+fn column(
+    title: &str,
+    factory: &gtk::ListItemFactory,
+    sorter: &gtk::Sorter,
+) -> gtk::ColumnViewColumn {
+    gtk::ColumnViewColumn::builder()
+        .title(title)
+        .factory(factory)
+        .sorter(sorter)
+        .resizable(true)
+        .expand(true)
+        .build()
+}
+
+impl FileSelection {
+    fn new(model: &impl IsA<gio::ListModel>, store: &DuplicatesStore) -> Self {
+        let selection: Self = glib::Object::builder().build();
+        let private = selection.imp();
+        private.model.get_or_init(|| model.clone().upcast());
+        private.store.get_or_init(|| store.clone());
+        model.connect_items_changed(
+            clone!(@weak selection => move |_model, position, removed, added| {
+                selection.items_changed(position, removed, added);
+            }),
+        );
+        selection
+    }
+
+    fn store(&self) -> &DuplicatesStore {
+        self.imp().store.get().unwrap()
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let group = self.take_group()?;
-        let files = self.take_files();
-        Some((group, files))
+    fn file_at(&self, position: u32) -> Option<DuplicateFile> {
+        let row = self.item(position).and_downcast::<gtk::TreeListRow>()?;
+        row.item().and_downcast()
+    }
+
+    /// Redraws selection of all rows.
+    fn changed(&self) {
+        self.selection_changed(0, self.n_items());
+    }
+
+    fn set_selection(&self, selected: &gtk::Bitset, mask: &gtk::Bitset) {
+        let store = self.store();
+        if mask.size() >= self.n_items() as u64 {
+            // e.g. a click on a row, files of collapsed groups are unselected as well
+            store.unselect_all();
+            for position in selected.to_vec() {
+                if let Some(file) = self.file_at(position) {
+                    store.select(&file, true);
+                }
+            }
+            self.changed();
+        } else if !mask.is_empty() {
+            for position in mask.to_vec() {
+                if let Some(file) = self.file_at(position) {
+                    store.select(&file, selected.contains(position));
+                }
+            }
+            let first = mask.minimum();
+            self.selection_changed(first, mask.maximum() - first + 1);
+        }
     }
 }
 
 #[derive(Clone)]
 pub struct DuplicatesList {
     scrolled_window: gtk::ScrolledWindow,
-    tree_view: gtk::TreeView,
+    column_view: gtk::ColumnView,
 }
 
 impl Default for DuplicatesList {
@@ -270,82 +1063,104 @@ impl Default for DuplicatesList {
 
 impl DuplicatesList {
     pub fn new() -> Self {
-        let tree_view = gtk::TreeView::builder()
+        let column_view = gtk::ColumnView::builder()
             .can_focus(true)
             .hexpand(true)
             .vexpand(true)
-            .headers_visible(true)
+            .show_column_separators(false)
             .build();
 
-        fn column(title: &str, text_column: StoreColumn) -> gtk::TreeViewColumn {
-            let column = gtk::TreeViewColumn::builder()
-                .sizing(gtk::TreeViewColumnSizing::Autosize)
-                .expand(true)
-                .title(title)
-                .build();
-            let text = gtk::CellRendererText::new();
-            CellLayoutExt::pack_start(&column, &text, true);
-            column.add_attribute(&text, "text", text_column as i32);
-            column.add_attribute(&text, "background-set", StoreColumn::IsGroup as i32);
-            column.add_attribute(&text, "background", StoreColumn::Background as i32);
-            column
-        }
-
-        tree_view.append_column(&column("Name", StoreColumn::Name));
-        tree_view.append_column(&column("Directory", StoreColumn::Directory));
-        tree_view.append_column(&column("Date", StoreColumn::Time));
-
-        let selection = tree_view.selection();
-        selection.set_mode(gtk::SelectionMode::Multiple);
-        selection.set_select_function(|_selection, model: &gtk::TreeModel, path, _selected| {
-            let iter = model.iter(path).unwrap();
-            let is_group = model.get::<bool>(&iter, StoreColumn::IsGroup as i32);
-            !is_group
-        });
+        column_view.append_column(&column(
+            "Name",
+            &name_factory(),
+            &sorter(
+                |a, b| a.imp().total.get().cmp(&b.imp().total.get()),
+                |a, b| a.name().cmp(&b.name()),
+            ),
+        ));
+        column_view.append_column(&column(
+            "Directory",
+            &text_factory(|row| match row {
+                Row::Group(group) => group.details(),
+                Row::File(file) => file.directory(),
+            }),
+            &sorter(
+                |a, b| {
+                    a.imp()
+                        .wasted
+                        .get()
+                        .logical
+                        .cmp(&b.imp().wasted.get().logical)
+                },
+                |a, b| a.directory().cmp(&b.directory()),
+            ),
+        ));
+        column_view.append_column(&column(
+            "Date",
+            &text_factory(|row| match row {
+                Row::Group(_) => String::new(),
+                Row::File(file) => file.date(),
+            }),
+            &sorter(
+                |_, _| Ordering::Equal,
+                |a, b| a.imp().modified.get().cmp(&b.imp().modified.get()),
+            ),
+        ));
+        column_view.append_column(&column(
+            "Size",
+            &text_factory(|row| match row {
+                Row::Group(group) => group.imp().total.get().to_string(),
+                Row::File(file) => file.imp().size.get().to_string(),
+            }),
+            &sorter(
+                |a, b| a.imp().total.get().cmp(&b.imp().total.get()),
+                |a, b| a.imp().size.get().cmp(&b.imp().size.get()),
+            ),
+        ));
 
-        let scrolled_window = scrolled(&tree_view, false);
-        scrolled_window.set_child(Some(&tree_view));
+        let scrolled_window = scrolled(&column_view, false);
 
         Self {
             scrolled_window,
-            tree_view,
+            column_view,
         }
     }
 
+    /// Shows groups of the store as collapsed rows. Rows are created only when they are
+    /// scrolled into view, and sorting is done in chunks, so large results stay responsive.
     pub fn set_model(&self, model: &DuplicatesStore) {
-        self.tree_view.set_model(Some(&model.to_model()));
+        let tree = gtk::TreeListModel::new(&model.to_model(), false, false, |item| {
+            item.downcast_ref::<DuplicateGroup>()
+                .map(|group| group.files_model().clone().upcast())
+        });
+        let sorter = gtk::TreeListRowSorter::new(self.column_view.sorter().as_ref());
+        let sorted = gtk::SortListModel::new(Some(&tree), Some(&sorter));
+        sorted.set_incremental(true);
+        let selection = FileSelection::new(&sorted, model);
+        self.column_view.set_model(Some(&selection));
+    }
+
+    fn selection(&self) -> Option<FileSelection> {
+        self.column_view.model().and_downcast()
     }
 
     pub fn set_popup(&self, popup_model: &gio::MenuModel) {
         let popup = gtk::PopoverMenu::from_model(Some(popup_model));
-        popup.set_parent(&self.tree_view);
+        popup.set_parent(&self.column_view);
 
         let popup_click = gtk::GestureClick::builder().build();
         popup_click.set_button(GDK_BUTTON_SECONDARY as u32);
-        self.tree_view.add_controller(&popup_click);
+        self.column_view.add_controller(&popup_click);
 
         popup_click.connect_pressed(
-            clone!(@weak self.tree_view as view, @weak popup => move |_gesture, _n, x, y| {
+            clone!(@weak self.column_view as view, @weak popup => move |_gesture, _n, x, y| {
                 view.grab_focus();
-
-                if let Some((Some(path), _, _, _)) = view.path_at_pos(x as i32, y as i32) {
-                    gtk::prelude::TreeViewExt::set_cursor(&view, &path, None::<&gtk::TreeViewColumn>, false);
-
-                    let row_rect = view.cell_area(Some(&path), None);
-                    popup.set_pointing_to(Some(&gdk::Rectangle::new(
-                        x as i32,
-                        row_rect.y(),
-                        0,
-                        row_rect.height(),
-                    )));
-                 } else {
-                    popup.set_pointing_to(Some(&gdk::Rectangle::new(
-                        x as i32,
-                        y as i32,
-                        0,
-                        0,
-                    )));
-                }
+                popup.set_pointing_to(Some(&gdk::Rectangle::new(
+                    x as i32,
+                    y as i32,
+                    0,
+                    0,
+                )));
                 popup.popup();
             }),
         );
@@ -355,20 +1170,68 @@ impl DuplicatesList {
         self.scrolled_window.clone().upcast()
     }
 
-    pub fn get_selection(&self) -> gtk::TreeSelection {
-        self.tree_view.selection()
+    /// Selects or unselects files of shown groups, collapsed ones included. `selected` gets a
+    /// file and whether it is selected now, and tells whether it should be.
+    pub fn update_selection(&self, selected: impl FnMut(&DuplicateFile, bool) -> bool) {
+        if let Some(selection) = self.selection() {
+            selection.store().update_selection(selected);
+            selection.changed();
+        }
     }
 
-    pub fn get_selected_iters(&self) -> Vec<gtk::TreeIter> {
-        let (selected, model) = self.tree_view.selection().selected_rows();
-        selected
-            .into_iter()
-            .filter_map(|tree_path| model.iter(&tree_path))
-            .collect()
+    pub fn unselect_all(&self) {
+        if let Some(selection) = self.selection() {
+            selection.store().unselect_all();
+            selection.changed();
+        }
+    }
+
+    /// Position of the row of the item among rows from `start` on.
+    fn find_row(&self, item: &glib::Object, start: u32) -> Option<(u32, gtk::TreeListRow)> {
+        let model = self.column_view.model()?;
+        (start..model.n_items()).find_map(|position| {
+            let row = model.item(position).and_downcast::<gtk::TreeListRow>()?;
+            (row.item().as_ref() == Some(item)).then_some((position, row))
+        })
+    }
+
+    /// Expands the group of the file and scrolls the list to the row of the file, if it is
+    /// shown.
+    pub fn scroll_to(&self, file: &DuplicateFile) {
+        let Some(group) = file.group() else {
+            return;
+        };
+        let Some((start, group_row)) = self.find_row(group.upcast_ref(), 0) else {
+            return;
+        };
+        group_row.set_expanded(true);
+        let Some((position, _)) = self.find_row(file.upcast_ref(), start) else {
+            return;
+        };
+        // ColumnView cannot scroll to an item in GTK 4.8, but the list view inside of it can.
+        let mut child = self.column_view.first_child();
+        while let Some(widget) = child {
+            if widget.is::<gtk::ListView>() {
+                if let Err(error) =
+                    widget.activate_action("list.scroll-to-item", Some(&position.to_variant()))
+                {
+                    eprintln!("Cannot scroll to a row: {}", error);
+                }
+                return;
+            }
+            child = widget.next_sibling();
+        }
+    }
+
+    /// Selected files of shown groups, collapsed ones included.
+    pub fn get_selected_files(&self) -> Vec<DuplicateFile> {
+        self.selection()
+            .map(|selection| selection.store().selected_files())
+            .unwrap_or_default()
     }
 
-    pub fn get_selected_iter(&self) -> Option<gtk::TreeIter> {
-        let mut selected = self.get_selected_iters();
+    pub fn get_selected_file(&self) -> Option<DuplicateFile> {
+        let mut selected = self.get_selected_files();
         if selected.len() == 1 {
             selected.pop()
         } else {
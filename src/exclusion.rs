@@ -1,23 +1,66 @@
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::error::Error;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
 use std::string::ToString;
 
-#[derive(Clone, Serialize, Deserialize)]
+/// Glob pattern or regular expression with options of matching.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PathPattern {
+    pub pattern: String,
+    pub case_sensitive: bool,
+    /// Pattern is matched against a file name instead of a full path.
+    pub basename: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Exclusion {
+    /// Directory with all its content.
     Directory(PathBuf),
-    Pattern(String),
+    /// Glob pattern.
+    Pattern(PathPattern),
+    Regex(PathPattern),
+    /// Files which do not match the regular expression are excluded. If there are several
+    /// inclusions, a file is searched when it matches any of them.
+    IncludeRegex(PathPattern),
+}
+
+impl PathPattern {
+    fn describe(&self, text: String) -> String {
+        let mut text = text;
+        if self.basename {
+            text.push_str(" [name]");
+        }
+        if !self.case_sensitive {
+            text.push_str(" [ignore case]");
+        }
+        text
+    }
 }
 
 impl ToString for Exclusion {
     fn to_string(&self) -> String {
         match self {
             Self::Directory(dir) => dir.display().to_string(),
-            Self::Pattern(pattern) => pattern.clone(),
+            Self::Pattern(pattern) => pattern.describe(pattern.pattern.clone()),
+            Self::Regex(pattern) => pattern.describe(format!("/{}/", pattern.pattern)),
+            Self::IncludeRegex(pattern) => {
+                pattern.describe(format!("include /{}/", pattern.pattern))
+            }
         }
     }
 }
 
+fn name_pattern(pattern: &str) -> Exclusion {
+    Exclusion::Pattern(PathPattern {
+        pattern: pattern.into(),
+        case_sensitive: true,
+        basename: true,
+    })
+}
+
 lazy_static! {
     pub static ref DEFAULT_EXCLUDE_PATTERNS: [Exclusion; 12] = [
         Exclusion::Directory("/lost+found".into()),
@@ -25,12 +68,188 @@ lazy_static! {
         Exclusion::Directory("/proc".into()),
         Exclusion::Directory("/sys".into()),
         Exclusion::Directory("/tmp".into()),
-        Exclusion::Pattern("*/.svn".into()),
-        Exclusion::Pattern("*/CVS".into()),
-        Exclusion::Pattern("*/.git".into()),
-        Exclusion::Pattern("*/.hg".into()),
-        Exclusion::Pattern("*/.bzr".into()),
-        Exclusion::Pattern("*/node_modules".into()),
-        Exclusion::Pattern("*/target".into()),
+        name_pattern(".svn"),
+        name_pattern("CVS"),
+        name_pattern(".git"),
+        name_pattern(".hg"),
+        name_pattern(".bzr"),
+        name_pattern("node_modules"),
+        name_pattern("target"),
     ];
 }
+
+pub enum ExclusionMatcher {
+    /// Canonical path of a directory.
+    Prefix(PathBuf),
+    Glob {
+        pattern: glob::Pattern,
+        options: glob::MatchOptions,
+        basename: bool,
+    },
+    Regex {
+        regex: regex::bytes::Regex,
+        basename: bool,
+    },
+}
+
+fn build_regex(pattern: &PathPattern) -> Result<ExclusionMatcher, regex::Error> {
+    let regex = regex::bytes::RegexBuilder::new(&pattern.pattern)
+        .case_insensitive(!pattern.case_sensitive)
+        .build()?;
+    Ok(ExclusionMatcher::Regex {
+        regex,
+        basename: pattern.basename,
+    })
+}
+
+/// Checks that the regular expression is valid.
+pub fn validate_regex(pattern: &str) -> Result<(), String> {
+    regex::bytes::Regex::new(pattern)
+        .map(|_| ())
+        .map_err(|error| error.to_string())
+}
+
+impl Exclusion {
+    pub fn is_inclusion(&self) -> bool {
+        matches!(self, Self::IncludeRegex(_))
+    }
+
+    pub fn matcher(&self) -> Result<ExclusionMatcher, Box<dyn Error>> {
+        let matcher = match self {
+            // directory which does not exist yet is matched literally
+            Self::Directory(dir) => {
+                ExclusionMatcher::Prefix(fs::canonicalize(dir).unwrap_or_else(|_| dir.clone()))
+            }
+            Self::Pattern(pattern) => ExclusionMatcher::Glob {
+                pattern: glob::Pattern::new(&pattern.pattern)?,
+                options: glob::MatchOptions {
+                    case_sensitive: pattern.case_sensitive,
+                    require_literal_separator: false,
+                    require_literal_leading_dot: false,
+                },
+                basename: pattern.basename,
+            },
+            Self::Regex(pattern) | Self::IncludeRegex(pattern) => build_regex(pattern)?,
+        };
+        Ok(matcher)
+    }
+}
+
+impl ExclusionMatcher {
+    /// Checks if the path is inside of the excluded directory.
+    pub fn is_inside(&self, real_path: &Path) -> bool {
+        matches!(self, Self::Prefix(dir) if real_path.starts_with(dir))
+    }
+
+    /// `real_path` is a canonical form of `path`.
+    pub fn matches(&self, path: &Path, real_path: &Path) -> bool {
+        match self {
+            Self::Prefix(dir) => real_path.starts_with(dir),
+            Self::Glob {
+                pattern,
+                options,
+                basename: true,
+            } => path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| pattern.matches_with(name, *options)),
+            Self::Glob {
+                pattern,
+                options,
+                basename: false,
+            } => pattern.matches_path_with(path, *options),
+            Self::Regex {
+                regex,
+                basename: true,
+            } => path
+                .file_name()
+                .is_some_and(|name| regex.is_match(name.as_bytes())),
+            Self::Regex {
+                regex,
+                basename: false,
+            } => regex.is_match(path.as_os_str().as_bytes()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(pattern: &str, case_sensitive: bool, basename: bool) -> PathPattern {
+        PathPattern {
+            pattern: pattern.to_string(),
+            case_sensitive,
+            basename,
+        }
+    }
+
+    fn matches(exclusion: Exclusion, path: &str) -> bool {
+        let path = Path::new(path);
+        exclusion.matcher().unwrap().matches(path, path)
+    }
+
+    #[test]
+    fn glob_patterns() {
+        let glob = |text: &str, case_sensitive: bool, basename: bool| {
+            Exclusion::Pattern(pattern(text, case_sensitive, basename))
+        };
+        assert!(matches(glob("*.jpg", true, true), "/photos/a.jpg"));
+        assert!(!matches(glob("*.jpg", true, true), "/photos/a.JPG"));
+        assert!(matches(glob("*.jpg", false, true), "/photos/a.JPG"));
+        assert!(!matches(glob("photos", true, true), "/photos/a.jpg"));
+        assert!(matches(
+            glob("/photos/*", true, false),
+            "/photos/2020/a.jpg"
+        ));
+        assert!(!matches(glob("*.jpg", true, false), "/photos/a.jpg.bak"));
+    }
+
+    #[test]
+    fn regular_expressions() {
+        let regex = |text: &str, case_sensitive: bool, basename: bool| {
+            Exclusion::Regex(pattern(text, case_sensitive, basename))
+        };
+        assert!(matches(regex("^tmp", true, true), "/data/tmp1"));
+        assert!(!matches(regex("^tmp", true, true), "/tmp/data"));
+        assert!(matches(regex("^/tmp/", true, false), "/tmp/data"));
+        assert!(!matches(regex(r"\.log$", true, false), "/var/A.LOG"));
+        assert!(matches(regex(r"\.log$", false, false), "/var/A.LOG"));
+        assert!(matches(regex(r"\.log$", false, true), "/var/A.LOG"));
+    }
+
+    #[test]
+    fn inclusions() {
+        let include = Exclusion::IncludeRegex(pattern(r"\.(jpe?g|png)$", false, true));
+        assert!(include.is_inclusion());
+        assert!(!Exclusion::Regex(pattern("x", true, true)).is_inclusion());
+        assert!(matches(include.clone(), "/photos/a.JPEG"));
+        assert!(!matches(include, "/photos/a.txt"));
+    }
+
+    #[test]
+    fn directories() {
+        let matcher = Exclusion::Directory("/no/such/dir".into())
+            .matcher()
+            .unwrap();
+        assert!(matcher.is_inside(Path::new("/no/such/dir/a")));
+        assert!(matcher.matches(Path::new("/link/a"), Path::new("/no/such/dir/a")));
+        assert!(!matcher.is_inside(Path::new("/no/such/directory")));
+        assert!(!Exclusion::Pattern(pattern("*", true, true))
+            .matcher()
+            .unwrap()
+            .is_inside(Path::new("/a")));
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        assert!(Exclusion::Pattern(pattern("[", true, true))
+            .matcher()
+            .is_err());
+        assert!(Exclusion::Regex(pattern("(", true, true))
+            .matcher()
+            .is_err());
+        assert!(validate_regex("(").is_err());
+        assert!(validate_regex("a+").is_ok());
+    }
+}
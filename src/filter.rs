@@ -0,0 +1,594 @@
+use crate::find_duplicates::FileInfo;
+use chrono::{Local, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+const IMAGE_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "gif", "webp", "bmp", "tif", "tiff", "heic", "svg",
+];
+const AUDIO_EXTENSIONS: &[&str] = &[
+    "mp3", "flac", "ogg", "oga", "opus", "wav", "m4a", "aac", "mka", "wma",
+];
+const VIDEO_EXTENSIONS: &[&str] = &[
+    "mp4", "m4v", "mkv", "avi", "mov", "webm", "wmv", "mpg", "mpeg", "3gp",
+];
+
+const DURATION_UNITS: [(&str, u64); 6] = [
+    ("y", 365 * 24 * 3600),
+    ("mo", 30 * 24 * 3600),
+    ("w", 7 * 24 * 3600),
+    ("d", 24 * 3600),
+    ("h", 3600),
+    ("min", 60),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Time {
+    /// Seconds before the scan.
+    Age(u64),
+    /// Seconds since Unix epoch.
+    Date(i64),
+}
+
+impl Time {
+    fn parse(text: &str) -> Result<Self, String> {
+        if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+            let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+            let time = Local
+                .from_local_datetime(&midnight)
+                .earliest()
+                .ok_or_else(|| format!("Invalid date {}.", text))?;
+            return Ok(Self::Date(time.timestamp()));
+        }
+        let split = text
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len());
+        let (number, unit) = text.split_at(split);
+        let number: u64 = number
+            .parse()
+            .map_err(|_| format!("\"{}\" is neither a date nor an age.", text))?;
+        let (_, seconds) = DURATION_UNITS
+            .iter()
+            .find(|(name, _)| *name == unit)
+            .ok_or_else(|| format!("Unknown unit of age \"{}\".", unit))?;
+        let time = number
+            .checked_mul(*seconds)
+            .map(Self::Age)
+            .filter(|time| time.resolve(SystemTime::now()).is_some())
+            .ok_or_else(|| format!("Age \"{}\" is too large.", text))?;
+        Ok(time)
+    }
+
+    /// `None` for times too far in the past to be represented.
+    fn resolve(self, now: SystemTime) -> Option<SystemTime> {
+        match self {
+            Self::Age(seconds) => now.checked_sub(Duration::from_secs(seconds)),
+            Self::Date(timestamp) if timestamp >= 0 => {
+                SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(timestamp as u64))
+            }
+            Self::Date(timestamp) => {
+                SystemTime::UNIX_EPOCH.checked_sub(Duration::from_secs(timestamp.unsigned_abs()))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Age(seconds) => {
+                let (name, unit) = DURATION_UNITS
+                    .iter()
+                    .find(|(_, unit)| seconds % unit == 0)
+                    .unwrap_or(&DURATION_UNITS[DURATION_UNITS.len() - 1]);
+                write!(f, "{}{}", seconds / unit, name)
+            }
+            Self::Date(timestamp) => match Local.timestamp_opt(*timestamp, 0).earliest() {
+                Some(date) => write!(f, "{}", date.format("%Y-%m-%d")),
+                None => write!(f, "{}", timestamp),
+            },
+        }
+    }
+}
+
+/// Permission bits test, as `find -perm`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Permissions {
+    /// Exactly these bits.
+    Exact(u32),
+    /// All of these bits are set.
+    All(u32),
+    /// Any of these bits is set.
+    Any(u32),
+}
+
+impl Permissions {
+    fn parse(text: &str) -> Result<Self, String> {
+        let parse_mode = |mode: &str| {
+            u32::from_str_radix(mode, 8)
+                .ok()
+                .filter(|mode| *mode <= 0o7777)
+                .ok_or_else(|| format!("\"{}\" is not an octal mode.", mode))
+        };
+        if let Some(mode) = text.strip_prefix('-') {
+            Ok(Self::All(parse_mode(mode)?))
+        } else if let Some(mode) = text.strip_prefix('/') {
+            Ok(Self::Any(parse_mode(mode)?))
+        } else {
+            Ok(Self::Exact(parse_mode(text)?))
+        }
+    }
+
+    fn matches(self, mode: u32) -> bool {
+        let mode = mode & 0o7777;
+        match self {
+            Self::Exact(bits) => mode == bits,
+            Self::All(bits) => mode & bits == bits,
+            Self::Any(bits) => bits == 0 || mode & bits != 0,
+        }
+    }
+}
+
+impl fmt::Display for Permissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exact(bits) => write!(f, "{:o}", bits),
+            Self::All(bits) => write!(f, "-{:o}", bits),
+            Self::Any(bits) => write!(f, "/{:o}", bits),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FileType {
+    Regular,
+    Symlink,
+    ArchiveMember,
+}
+
+impl FileType {
+    fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "f" => Ok(Self::Regular),
+            "l" => Ok(Self::Symlink),
+            "a" => Ok(Self::ArchiveMember),
+            _ => Err(format!("Unknown file type \"{}\", use f, l or a.", text)),
+        }
+    }
+
+    fn of(fi: &FileInfo) -> Self {
+        if fi.is_archive_member() {
+            Self::ArchiveMember
+        } else if fi.is_symlink() {
+            Self::Symlink
+        } else {
+            Self::Regular
+        }
+    }
+}
+
+impl fmt::Display for FileType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Regular => "f",
+            Self::Symlink => "l",
+            Self::ArchiveMember => "a",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Condition on files. Only files which satisfy the filter are searched.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Filter {
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+    OlderThan(Time),
+    NewerThan(Time),
+    /// Extensions in lower case or names of extension sets (`@image`, `@audio`, `@video`,
+    /// `@media`).
+    Extension(Vec<String>),
+    Uid(u32),
+    Gid(u32),
+    Permissions(Permissions),
+    Type(FileType),
+}
+
+fn extension_matches(pattern: &str, ext: &str) -> bool {
+    let set = match pattern {
+        "@image" => IMAGE_EXTENSIONS,
+        "@audio" => AUDIO_EXTENSIONS,
+        "@video" => VIDEO_EXTENSIONS,
+        "@media" => {
+            return ["@image", "@audio", "@video"]
+                .iter()
+                .any(|set| extension_matches(set, ext))
+        }
+        _ => return pattern == ext,
+    };
+    set.contains(&ext)
+}
+
+impl Filter {
+    pub fn matches(&self, fi: &FileInfo, now: SystemTime) -> bool {
+        match self {
+            Self::And(filters) => filters.iter().all(|filter| filter.matches(fi, now)),
+            Self::Or(filters) => filters.iter().any(|filter| filter.matches(fi, now)),
+            Self::Not(filter) => !filter.matches(fi, now),
+            Self::OlderThan(time) => time.resolve(now).is_some_and(|time| fi.modified < time),
+            Self::NewerThan(time) => time.resolve(now).is_none_or(|time| fi.modified > time),
+            Self::Extension(patterns) => {
+                let Some(ext) = fi.path.extension().and_then(|ext| ext.to_str()) else {
+                    return false;
+                };
+                let ext = ext.to_lowercase();
+                patterns
+                    .iter()
+                    .any(|pattern| extension_matches(pattern, &ext))
+            }
+            Self::Uid(uid) => fi.uid == *uid,
+            Self::Gid(gid) => fi.gid == *gid,
+            Self::Permissions(permissions) => permissions.matches(fi.mode),
+            Self::Type(file_type) => FileType::of(fi) == *file_type,
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |f: &mut fmt::Formatter<'_>, filters: &[Filter], separator: &str| {
+            f.write_str("(")?;
+            for (index, filter) in filters.iter().enumerate() {
+                if index > 0 {
+                    f.write_str(separator)?;
+                }
+                write!(f, "{}", filter)?;
+            }
+            f.write_str(")")
+        };
+        match self {
+            Self::And(filters) => join(f, filters, " and "),
+            Self::Or(filters) => join(f, filters, " or "),
+            Self::Not(filter) => write!(f, "not {}", filter),
+            Self::OlderThan(time) => write!(f, "older:{}", time),
+            Self::NewerThan(time) => write!(f, "newer:{}", time),
+            Self::Extension(patterns) => write!(f, "ext:{}", patterns.join(",")),
+            Self::Uid(uid) => write!(f, "uid:{}", uid),
+            Self::Gid(gid) => write!(f, "gid:{}", gid),
+            Self::Permissions(permissions) => write!(f, "perm:{}", permissions),
+            Self::Type(file_type) => write!(f, "type:{}", file_type),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (index, c) in text.char_indices() {
+        if c.is_whitespace() || c == '(' || c == ')' {
+            if let Some(start) = start.take() {
+                tokens.push(&text[start..index]);
+            }
+            if !c.is_whitespace() {
+                tokens.push(&text[index..index + 1]);
+            }
+        } else if start.is_none() {
+            start = Some(index);
+        }
+    }
+    if let Some(start) = start {
+        tokens.push(&text[start..]);
+    }
+    tokens
+}
+
+struct Parser<'t> {
+    tokens: Vec<&'t str>,
+    position: usize,
+}
+
+impl<'t> Parser<'t> {
+    fn peek(&self) -> Option<&'t str> {
+        self.tokens.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<&'t str> {
+        let token = self.peek();
+        self.position += 1;
+        token
+    }
+
+    fn is_keyword(token: Option<&str>, keyword: &str) -> bool {
+        token.is_some_and(|token| token.eq_ignore_ascii_case(keyword))
+    }
+
+    fn or(&mut self) -> Result<Filter, String> {
+        let mut filters = vec![self.and()?];
+        while Self::is_keyword(self.peek(), "or") {
+            self.next();
+            filters.push(self.and()?);
+        }
+        Ok(if filters.len() == 1 {
+            filters.remove(0)
+        } else {
+            Filter::Or(filters)
+        })
+    }
+
+    /// Terms without an operator between them are joined by `and`.
+    fn and(&mut self) -> Result<Filter, String> {
+        let mut filters = vec![self.unary()?];
+        loop {
+            let token = self.peek();
+            if Self::is_keyword(token, "and") {
+                self.next();
+            } else if token.is_none() || token == Some(")") || Self::is_keyword(token, "or") {
+                break;
+            }
+            filters.push(self.unary()?);
+        }
+        Ok(if filters.len() == 1 {
+            filters.remove(0)
+        } else {
+            Filter::And(filters)
+        })
+    }
+
+    fn unary(&mut self) -> Result<Filter, String> {
+        match self.next() {
+            None => Err("Unexpected end of filter.".to_string()),
+            Some(token) if Self::is_keyword(Some(token), "not") => {
+                Ok(Filter::Not(Box::new(self.unary()?)))
+            }
+            Some("(") => {
+                let filter = self.or()?;
+                if self.next() != Some(")") {
+                    return Err("Missing closing parenthesis.".to_string());
+                }
+                Ok(filter)
+            }
+            Some(token) => predicate(token),
+        }
+    }
+}
+
+fn predicate(token: &str) -> Result<Filter, String> {
+    let (name, argument) = token.split_once(':').ok_or_else(|| {
+        format!(
+            "Expected predicate (like ext:jpg) instead of \"{}\".",
+            token
+        )
+    })?;
+    let id = |argument: &str| {
+        argument
+            .parse::<u32>()
+            .map_err(|_| format!("\"{}\" is not a numeric id.", argument))
+    };
+    match name {
+        "older" => Ok(Filter::OlderThan(Time::parse(argument)?)),
+        "newer" => Ok(Filter::NewerThan(Time::parse(argument)?)),
+        "ext" => {
+            let extensions: Vec<String> = argument
+                .split(',')
+                .map(|ext| ext.trim_start_matches('.').to_lowercase())
+                .filter(|ext| !ext.is_empty())
+                .collect();
+            if extensions.is_empty() {
+                return Err("Empty list of extensions.".to_string());
+            }
+            Ok(Filter::Extension(extensions))
+        }
+        "uid" => Ok(Filter::Uid(id(argument)?)),
+        "gid" => Ok(Filter::Gid(id(argument)?)),
+        "perm" => Ok(Filter::Permissions(Permissions::parse(argument)?)),
+        "type" => Ok(Filter::Type(FileType::parse(argument)?)),
+        _ => Err(format!("Unknown predicate \"{}\".", name)),
+    }
+}
+
+/// Parses a filter expression. Predicates are `older:AGE`, `newer:AGE` (AGE is a number with
+/// unit `min`, `h`, `d`, `w`, `mo`, `y` or a date YYYY-MM-DD), `ext:jpg,png` (or sets `@image`,
+/// `@audio`, `@video`, `@media`), `uid:N`, `gid:N`, `perm:MODE` (octal as in `find -perm`) and
+/// `type:f|l|a` (regular file, symbolic link, archive member). They are combined with `and`,
+/// `or`, `not` and parentheses. Empty expression means no filter.
+pub fn parse_filter(text: &str) -> Result<Option<Filter>, String> {
+    let mut parser = Parser {
+        tokens: tokenize(text),
+        position: 0,
+    };
+    if parser.peek().is_none() {
+        return Ok(None);
+    }
+    let filter = parser.or()?;
+    if let Some(token) = parser.peek() {
+        return Err(format!("Unexpected \"{}\".", token));
+    }
+    Ok(Some(filter))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn file(path: &str) -> FileInfo {
+        FileInfo {
+            path: PathBuf::from(path),
+            modified: SystemTime::now() - Duration::from_secs(3600),
+            size: 1,
+            disk_usage: 512,
+            device: 1,
+            inode: 1,
+            uid: 1000,
+            gid: 100,
+            mode: 0o100644,
+            archive_member: None,
+            link_target: None,
+            nlink: 1,
+            hard_links: Vec::new(),
+        }
+    }
+
+    fn parse(text: &str) -> Filter {
+        parse_filter(text).unwrap().unwrap()
+    }
+
+    fn ext(extension: &str) -> Filter {
+        Filter::Extension(vec![extension.to_string()])
+    }
+
+    #[test]
+    fn tokens() {
+        assert_eq!(
+            tokenize("not(ext:a or  ext:b)ext:c"),
+            ["not", "(", "ext:a", "or", "ext:b", ")", "ext:c"]
+        );
+        assert!(tokenize("  ").is_empty());
+    }
+
+    #[test]
+    fn empty_filter() {
+        assert_eq!(parse_filter(""), Ok(None));
+        assert_eq!(parse_filter("  "), Ok(None));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(
+            parse("ext:a or ext:b and ext:c"),
+            Filter::Or(vec![ext("a"), Filter::And(vec![ext("b"), ext("c")])])
+        );
+        assert_eq!(
+            parse("ext:a ext:b OR ext:c"),
+            Filter::Or(vec![Filter::And(vec![ext("a"), ext("b")]), ext("c")])
+        );
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and() {
+        assert_eq!(
+            parse("not ext:a and ext:b"),
+            Filter::And(vec![Filter::Not(Box::new(ext("a"))), ext("b")])
+        );
+        assert_eq!(
+            parse("not not ext:a"),
+            Filter::Not(Box::new(Filter::Not(Box::new(ext("a")))))
+        );
+    }
+
+    #[test]
+    fn parentheses() {
+        assert_eq!(
+            parse("(ext:a or ext:b) and ext:c"),
+            Filter::And(vec![Filter::Or(vec![ext("a"), ext("b")]), ext("c")])
+        );
+        assert_eq!(
+            parse("not (ext:a ext:b)"),
+            Filter::Not(Box::new(Filter::And(vec![ext("a"), ext("b")])))
+        );
+        assert_eq!(parse("((ext:a))"), ext("a"));
+    }
+
+    #[test]
+    fn predicates() {
+        assert_eq!(
+            parse("ext:.JPG,png,"),
+            Filter::Extension(vec!["jpg".to_string(), "png".to_string()])
+        );
+        assert_eq!(
+            parse("older:2w"),
+            Filter::OlderThan(Time::Age(14 * 24 * 3600))
+        );
+        assert_eq!(parse("newer:30min"), Filter::NewerThan(Time::Age(30 * 60)));
+        assert!(matches!(
+            parse("newer:2020-01-31"),
+            Filter::NewerThan(Time::Date(_))
+        ));
+        assert_eq!(parse("uid:1000"), Filter::Uid(1000));
+        assert_eq!(parse("gid:0"), Filter::Gid(0));
+        assert_eq!(
+            parse("perm:-644"),
+            Filter::Permissions(Permissions::All(0o644))
+        );
+        assert_eq!(
+            parse("perm:/111"),
+            Filter::Permissions(Permissions::Any(0o111))
+        );
+        assert_eq!(
+            parse("perm:755"),
+            Filter::Permissions(Permissions::Exact(0o755))
+        );
+        assert_eq!(parse("type:l"), Filter::Type(FileType::Symlink));
+    }
+
+    #[test]
+    fn matching() {
+        let now = SystemTime::now();
+        let photo = file("/photos/a.JPG");
+        let matches = |text: &str, fi: &FileInfo| parse(text).matches(fi, now);
+
+        assert!(matches("ext:jpg", &photo));
+        assert!(matches("ext:@image", &photo));
+        assert!(matches("ext:@media", &photo));
+        assert!(!matches("ext:@audio", &photo));
+        assert!(!matches("ext:jpg", &file("/photos/jpg")));
+        assert!(matches("older:30min", &photo));
+        assert!(!matches("older:2h", &photo));
+        assert!(matches("newer:2h", &photo));
+        assert!(matches("uid:1000 gid:100", &photo));
+        assert!(!matches("uid:0 or gid:0", &photo));
+        assert!(matches("perm:644", &photo));
+        assert!(matches("perm:-604", &photo));
+        assert!(!matches("perm:-700", &photo));
+        assert!(matches("perm:/222", &photo));
+        assert!(!matches("perm:/111", &photo));
+        assert!(matches("type:f", &photo));
+        assert!(!matches("type:l", &photo));
+        assert!(matches("not type:l and (ext:png or ext:jpg)", &photo));
+
+        let mut link = file("/photos/b.png");
+        link.link_target = Some(PathBuf::from("/photos/a.png"));
+        assert!(matches("type:l", &link));
+    }
+
+    #[test]
+    fn errors() {
+        let error = |text: &str| parse_filter(text).unwrap_err();
+        assert_eq!(error("ext:a and"), "Unexpected end of filter.");
+        assert_eq!(error("(ext:a"), "Missing closing parenthesis.");
+        assert_eq!(error("ext:a )"), "Unexpected \")\".");
+        assert_eq!(
+            error("jpg"),
+            "Expected predicate (like ext:jpg) instead of \"jpg\"."
+        );
+        assert_eq!(error("size:1"), "Unknown predicate \"size\".");
+        assert_eq!(error("ext:,"), "Empty list of extensions.");
+        assert_eq!(error("older:3x"), "Unknown unit of age \"x\".");
+        assert_eq!(error("older:d"), "\"d\" is neither a date nor an age.");
+        assert_eq!(
+            error("older:99999999999999y"),
+            "Age \"99999999999999y\" is too large."
+        );
+        assert_eq!(error("uid:me"), "\"me\" is not a numeric id.");
+        assert_eq!(error("perm:800"), "\"800\" is not an octal mode.");
+        assert_eq!(error("type:d"), "Unknown file type \"d\", use f, l or a.");
+    }
+
+    #[test]
+    fn display_is_parsed_back() {
+        for text in [
+            "(ext:jpg,png or not (older:2w and perm:-644))",
+            "(uid:1 and gid:2 and type:a and newer:1h)",
+            "perm:/111",
+        ] {
+            assert_eq!(parse(text).to_string(), text);
+        }
+    }
+
+    #[test]
+    fn dates_out_of_range_are_displayed_as_numbers() {
+        assert_eq!(Time::Date(i64::MAX).to_string(), i64::MAX.to_string());
+    }
+}
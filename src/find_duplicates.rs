@@ -1,19 +1,34 @@
-use crate::exclusion::Exclusion;
+use crate::archives::{self, ArchiveMember};
+use crate::audio::{self, is_audio, AudioMatch};
+use crate::duplicate_directories::{find_duplicate_directories, DirectoryGroup};
+use crate::exclusion::{Exclusion, ExclusionMatcher};
+use crate::filter::Filter;
+use crate::ignore_files::IgnoreStack;
+use crate::mounts;
+use crate::normalizers::{self, normalizers};
+use crate::similar_images::{distance_to_similarity, group_similar_images, is_image, ImageHash};
 use humansize::{format_size, DECIMAL};
 use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
 use sha2::{
     digest::{generic_array::GenericArray, OutputSizeUser},
     Digest, Sha256,
 };
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{hash_map::Entry, HashMap, HashSet};
 use std::error::Error;
+use std::ffi::OsString;
 use std::fs::{self, Metadata};
 use std::hash::Hash;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::SystemTime;
 
-#[derive(Debug)]
+pub type FileHash = GenericArray<u8, <Sha256 as OutputSizeUser>::OutputSize>;
+
+#[derive(Clone, Debug)]
 pub struct FileInfo {
     pub path: PathBuf,
     pub modified: SystemTime,
@@ -21,6 +36,18 @@ pub struct FileInfo {
     pub disk_usage: u64,
     pub device: u64,
     pub inode: u64,
+    pub uid: u32,
+    pub gid: u32,
+    /// Permission bits and file type as in `st_mode`.
+    pub mode: u32,
+    /// Set for virtual files stored inside of archives.
+    pub archive_member: Option<ArchiveMember>,
+    /// Set for symbolic links. Other fields describe the file the link points to.
+    pub link_target: Option<PathBuf>,
+    /// Number of hard links to the file.
+    pub nlink: u64,
+    /// Other paths of the same file found by the search.
+    pub hard_links: Vec<PathBuf>,
 }
 
 impl FileInfo {
@@ -31,9 +58,14 @@ impl FileInfo {
         use std::os::unix::fs::MetadataExt;
         let modified = metadata.modified()?;
         let size = metadata.len();
-        let disk_usage = metadata.blocks() * metadata.blksize();
+        // blocks are 512 bytes regardless of the block size of the file system
+        let disk_usage = metadata.blocks() * 512;
         let device = metadata.dev();
         let inode = metadata.ino();
+        let uid = metadata.uid();
+        let gid = metadata.gid();
+        let mode = metadata.mode();
+        let nlink = metadata.nlink();
         Ok(FileInfo {
             path: path.into(),
             modified,
@@ -41,65 +73,399 @@ impl FileInfo {
             disk_usage,
             device,
             inode,
+            uid,
+            gid,
+            mode,
+            archive_member: None,
+            link_target: None,
+            nlink,
+            hard_links: Vec::new(),
         })
     }
+
+    pub fn is_archive_member(&self) -> bool {
+        self.archive_member.is_some()
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.link_target.is_some()
+    }
+
+    /// Space freed by removing all found paths of the file. Nothing is freed while the file has
+    /// hard links elsewhere.
+    pub fn freed(&self) -> Waste {
+        if self.nlink > 1 + self.hard_links.len() as u64 {
+            Waste::default()
+        } else {
+            Waste::of_file(self)
+        }
+    }
+
+    /// Key of the stored content. Paths with the same identity are the same file.
+    fn identity(&self) -> (u64, u64, Option<String>) {
+        let member = self
+            .archive_member
+            .as_ref()
+            .map(|member| member.name.clone());
+        (self.device, self.inode, member)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SymlinkPolicy {
+    /// Symbolic links are skipped.
+    Ignore,
+    /// Links to directories are walked, links to files are treated as the files.
+    Follow,
+    /// Links are not walked but shown next to the duplicates they point to.
+    Report,
+}
+
+impl SymlinkPolicy {
+    pub const ALL: [SymlinkPolicy; 3] = [Self::Ignore, Self::Follow, Self::Report];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Ignore => "ignore",
+            Self::Follow => "follow",
+            Self::Report => "report",
+        }
+    }
+}
+
+/// Flag to stop a running search from another thread. The search checks it between files and
+/// fails with `io::ErrorKind::Interrupted`.
+#[derive(Clone, Debug, Default)]
+pub struct Cancellation(Arc<AtomicBool>);
+
+impl Cancellation {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn check(&self) -> io::Result<()> {
+        if self.is_cancelled() {
+            Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "Search is cancelled.",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Modification time and names of entries of directories.
+type DirectoryEntries = HashMap<PathBuf, (SystemTime, Vec<OsString>)>;
+
+/// Values computed from content of files by path, valid while size and modification time are
+/// the same.
+#[derive(Clone, Debug)]
+pub struct ContentCache<T>(HashMap<PathBuf, (u64, SystemTime, T)>);
+
+impl<T> Default for ContentCache<T> {
+    fn default() -> Self {
+        Self(HashMap::new())
+    }
 }
 
-fn find_files(
-    dir: &Path,
-    files: &mut Vec<FileInfo>,
-    exclude: &[glob::Pattern],
-    min_size: u64,
-    recurse: bool,
-) -> io::Result<()> {
-    if dir.is_dir() {
-        for entry in fs::read_dir(dir)? {
-            let entry = entry?;
-            let path = entry.path();
+impl<T: Clone> ContentCache<T> {
+    /// Value for the file if the file has not changed since the value was computed.
+    pub fn get(&self, fi: &FileInfo) -> Option<T> {
+        let (size, modified, value) = self.0.get(&fi.path)?;
+        (*size == fi.size && *modified == fi.modified).then(|| value.clone())
+    }
+
+    /// Value for the file, computed by `compute` unless it is cached.
+    pub fn get_or_compute(
+        &mut self,
+        fi: &FileInfo,
+        compute: impl FnOnce(&FileInfo) -> io::Result<T>,
+    ) -> io::Result<T> {
+        if let Some(value) = self.get(fi) {
+            return Ok(value);
+        }
+        let value = compute(fi)?;
+        self.0
+            .insert(fi.path.clone(), (fi.size, fi.modified, value.clone()));
+        Ok(value)
+    }
+
+    /// Forgets files which are not among `paths`.
+    fn retain(&mut self, paths: &HashSet<&Path>) {
+        self.0.retain(|path, _| paths.contains(path.as_path()));
+    }
+}
+
+/// Hashes of whole files.
+type FileHashes = ContentCache<FileHash>;
+
+/// What a search has read, so that a refresh reads only changed directories and hashes and
+/// decodes only changed files.
+#[derive(Clone, Debug, Default)]
+pub struct ScanCache {
+    directories: DirectoryEntries,
+    hashes: FileHashes,
+    image_hashes: ContentCache<Option<u64>>,
+    fingerprints: ContentCache<Option<audio::Fingerprint>>,
+    tags: ContentCache<Option<audio::Tags>>,
+    normalized: ContentCache<Option<FileHash>>,
+}
+
+/// State of a walk over searched directories.
+struct Walker<'a> {
+    options: &'a SearchOptions,
+    exclude: Vec<&'a ExclusionMatcher>,
+    /// If not empty, only files matching any of these are searched.
+    include: &'a [ExclusionMatcher],
+    /// Devices of file systems which are not searched.
+    skipped_devices: HashSet<u64>,
+    /// Directories already walked, by (device, inode).
+    visited: HashSet<(u64, u64)>,
+    /// Time of the scan, ages in the filter are relative to it.
+    now: SystemTime,
+    ignores: Option<IgnoreStack>,
+    cancellation: &'a Cancellation,
+    /// Directories of a previous search.
+    previous: DirectoryEntries,
+    directories: DirectoryEntries,
+    files: Vec<FileInfo>,
+}
+
+impl<'a> Walker<'a> {
+    fn accepts(&self, fi: &FileInfo) -> bool {
+        self.options.accepts_size(fi.size)
+            && (self.include.is_empty()
+                || self
+                    .include
+                    .iter()
+                    .any(|inclusion| inclusion.matches(&fi.path, &fi.path)))
+            && self
+                .options
+                .filter
+                .as_ref()
+                .is_none_or(|filter| filter.matches(fi, self.now))
+    }
+
+    fn push_file(&mut self, fi: FileInfo) {
+        let options = self.options;
+        if options.search_archives && archives::is_archive(&fi.path) {
+            // unreadable archive is treated as a regular file, cancellation is noticed by the
+            // walk right after
+            if let Ok(members) = archives::list_members(&fi, self.cancellation) {
+                for member in members {
+                    let excluded = self
+                        .exclude
+                        .iter()
+                        .any(|exclusion| exclusion.matches(&member.path, &member.path));
+                    if !excluded && self.accepts(&member) {
+                        self.files.push(member);
+                    }
+                }
+            }
+        }
+        if self.accepts(&fi) {
+            self.files.push(fi);
+        }
+    }
+
+    /// Names of entries of `dir`. Directory is read only if it was modified since the previous
+    /// search.
+    fn entry_names(&mut self, dir: &Path, modified: SystemTime) -> io::Result<Vec<OsString>> {
+        let names = match self.previous.remove(dir) {
+            Some((previous_modified, names)) if previous_modified == modified => names,
+            _ => fs::read_dir(dir)?
+                .map(|entry| Ok(entry?.file_name()))
+                .collect::<io::Result<_>>()?,
+        };
+        self.directories
+            .insert(dir.to_owned(), (modified, names.clone()));
+        Ok(names)
+    }
+
+    /// Walks `dir`. `real_dir` is the canonical path of `dir`, `root_device` is the device of
+    /// the searched directory `dir` belongs to, `depth` is the depth of entries of `dir` (1 for
+    /// entries of the searched directory).
+    fn find_files(
+        &mut self,
+        dir: &Path,
+        real_dir: &Path,
+        root_device: u64,
+        depth: u32,
+    ) -> io::Result<()> {
+        use std::os::unix::fs::MetadataExt;
+
+        let options = self.options;
+        let Ok(dir_metadata) = fs::metadata(dir) else {
+            return Ok(());
+        };
+        let device = dir_metadata.dev();
+        if !dir_metadata.is_dir()
+            || self.skipped_devices.contains(&device)
+            || (options.one_file_system && device != root_device)
+        {
+            return Ok(());
+        }
+        // directory is reachable by several paths through symbolic links
+        if !self.visited.insert((device, dir_metadata.ino())) {
+            return Ok(());
+        }
+
+        if let Some(ignores) = &mut self.ignores {
+            ignores.push(dir);
+        }
+        let result = dir_metadata
+            .modified()
+            .and_then(|modified| self.entry_names(dir, modified))
+            .and_then(|names| {
+                self.find_files_in_entries(dir, real_dir, &names, root_device, depth)
+            });
+        if let Some(ignores) = &mut self.ignores {
+            ignores.pop();
+        }
+        result
+    }
 
-            let skip = exclude.iter().any(|pattern| pattern.matches_path(&path));
+    fn find_files_in_entries(
+        &mut self,
+        dir: &Path,
+        real_dir: &Path,
+        names: &[OsString],
+        root_device: u64,
+        depth: u32,
+    ) -> io::Result<()> {
+        use std::os::unix::fs::MetadataExt;
+
+        let options = self.options;
+        let exclude = self.exclude.clone();
+        for name in names {
+            self.cancellation.check()?;
+            let path = dir.join(name);
+            let real_path = real_dir.join(name);
+
+            let skip = exclude
+                .iter()
+                .any(|exclusion| exclusion.matches(&path, &real_path));
             if skip {
                 continue;
             }
 
-            let metadata = entry.metadata()?;
+            let metadata = match fs::symlink_metadata(&path) {
+                Ok(metadata) => metadata,
+                // removed since the directory was read
+                Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+                Err(error) => return Err(error),
+            };
+            let ignored = self
+                .ignores
+                .as_ref()
+                .is_some_and(|ignores| ignores.is_ignored(&path, metadata.is_dir()));
+            if ignored {
+                continue;
+            }
+
             if metadata.is_dir() {
-                if recurse {
-                    find_files(&path, files, exclude, min_size, recurse)?;
+                if options.descends_below(depth) {
+                    self.find_files(&path, &real_path, root_device, depth + 1)?;
                 }
             } else if metadata.is_file() {
                 let fi = FileInfo::from_path_and_metadata(path, metadata)?;
-                if fi.size >= min_size {
-                    files.push(fi);
+                self.push_file(fi);
+            } else if metadata.is_symlink() && options.symlinks != SymlinkPolicy::Ignore {
+                // dangling link
+                let Ok(target_metadata) = fs::metadata(&path) else {
+                    continue;
+                };
+                if target_metadata.is_dir() {
+                    if options.symlinks == SymlinkPolicy::Follow && options.descends_below(depth) {
+                        let Ok(real_target) = fs::canonicalize(&path) else {
+                            continue;
+                        };
+                        let excluded = exclude
+                            .iter()
+                            .any(|exclusion| exclusion.matches(&path, &real_target));
+                        if !excluded {
+                            self.find_files(&path, &real_target, root_device, depth + 1)?;
+                        }
+                    }
+                } else if target_metadata.is_file() {
+                    if self.skipped_devices.contains(&target_metadata.dev())
+                        || (options.one_file_system && target_metadata.dev() != root_device)
+                    {
+                        continue;
+                    }
+                    // replaced by another file since its metadata was read
+                    let Ok(target) = fs::read_link(&path) else {
+                        continue;
+                    };
+                    let mut fi = FileInfo::from_path_and_metadata(path, target_metadata)?;
+                    fi.link_target = Some(target);
+                    if options.symlinks == SymlinkPolicy::Follow {
+                        self.push_file(fi);
+                    } else if self.accepts(&fi) {
+                        self.files.push(fi);
+                    }
                 }
             }
         }
+        Ok(())
     }
-    Ok(())
 }
 
 fn find_files_in_dirs(
-    dirs: &[PathBuf],
-    exclude: &[glob::Pattern],
-    min_size: u64,
-    recurse: bool,
-) -> io::Result<Vec<FileInfo>> {
-    let mut files = Vec::new();
-    for dir in dirs {
-        find_files(dir, &mut files, exclude, min_size, recurse)?;
+    options: &SearchOptions,
+    exclude: &[ExclusionMatcher],
+    include: &[ExclusionMatcher],
+    previous: DirectoryEntries,
+    cancellation: &Cancellation,
+) -> io::Result<(Vec<FileInfo>, DirectoryEntries)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let mut walker = Walker {
+        options,
+        exclude: Vec::new(),
+        include,
+        skipped_devices: mounts::devices_of_types(&options.skip_file_systems)?,
+        visited: HashSet::new(),
+        now: SystemTime::now(),
+        ignores: None,
+        cancellation,
+        previous,
+        directories: HashMap::new(),
+        files: Vec::new(),
+    };
+    for dir in &options.paths {
+        let Ok(real_dir) = fs::canonicalize(dir) else {
+            continue;
+        };
+        // searched directory wins over excluded directories it is inside of
+        walker.exclude = exclude
+            .iter()
+            .filter(|exclusion| !exclusion.is_inside(&real_dir))
+            .collect();
+        if options.ignore_files {
+            walker.ignores = Some(IgnoreStack::new(dir));
+        }
+        let Ok(metadata) = fs::metadata(&real_dir) else {
+            continue;
+        };
+        walker.find_files(dir, &real_dir, metadata.dev(), 1)?;
     }
-    Ok(files)
+    Ok((walker.files, walker.directories))
 }
 
-fn get_file_hash(
-    fi: &FileInfo,
-) -> io::Result<GenericArray<u8, <Sha256 as OutputSizeUser>::OutputSize>> {
+fn get_file_hash(fi: &FileInfo) -> io::Result<FileHash> {
     lazy_static! {
-        static ref EMPTY_HASH: GenericArray<u8, <Sha256 as OutputSizeUser>::OutputSize> =
-            Sha256::new().finalize();
+        static ref EMPTY_HASH: FileHash = Sha256::new().finalize();
     }
 
-    if fi.size > 0 {
+    if let Some(member) = &fi.archive_member {
+        archives::member_hash(member)
+    } else if fi.size > 0 {
         let mut hasher = Sha256::new();
         let file = std::fs::File::open(&fi.path)?;
         let mut reader = io::BufReader::new(file);
@@ -111,6 +477,32 @@ fn get_file_hash(
     }
 }
 
+/// Collapses hard links of a file into one entry.
+fn merge_hard_links(files: Vec<FileInfo>) -> Vec<FileInfo> {
+    let mut merged: HashMap<_, FileInfo> = HashMap::new();
+    for fi in files {
+        match merged.entry(fi.identity()) {
+            Entry::Vacant(entry) => {
+                entry.insert(fi);
+            }
+            Entry::Occupied(mut entry) => {
+                let first = entry.get_mut();
+                // the same path is found twice when searched directories overlap
+                if first.path != fi.path && !first.hard_links.contains(&fi.path) {
+                    first.hard_links.push(fi.path);
+                }
+            }
+        }
+    }
+    merged
+        .into_values()
+        .map(|mut fi| {
+            fi.hard_links.sort();
+            fi
+        })
+        .collect()
+}
+
 fn unique_by<K: Hash + Eq>(
     get_key: impl Fn(&FileInfo) -> io::Result<K>,
     fis: Vec<FileInfo>,
@@ -140,8 +532,50 @@ fn group_by_size(fis: Vec<FileInfo>) -> io::Result<Vec<Vec<FileInfo>>> {
     group_by(|fi| Ok(fi.size), fis)
 }
 
-fn group_by_hash(fis: Vec<FileInfo>) -> io::Result<Vec<Vec<FileInfo>>> {
-    group_by(get_file_hash, fis)
+/// Hash of the file from `hashes` if the file has not changed since it was hashed.
+fn cached_hash(fi: &FileInfo, hashes: &RefCell<FileHashes>) -> Option<FileHash> {
+    hashes.borrow().get(fi)
+}
+
+/// Hash of the file, taken from `hashes` if the file has not changed since it was hashed.
+fn cached_file_hash(fi: &FileInfo, hashes: &RefCell<FileHashes>) -> io::Result<FileHash> {
+    hashes.borrow_mut().get_or_compute(fi, get_file_hash)
+}
+
+fn group_by_hash(
+    fis: Vec<FileInfo>,
+    hashes: &RefCell<FileHashes>,
+    cancellation: &Cancellation,
+) -> io::Result<Vec<Vec<FileInfo>>> {
+    group_by(
+        |fi| {
+            cancellation.check()?;
+            cached_file_hash(fi, hashes)
+        },
+        fis,
+    )
+}
+
+/// Hashes members of archives in the groups at once, so every archive is opened once.
+/// Members which cannot be read are left out, as unreadable archives are.
+fn hash_archive_members(
+    mut groups: Vec<Vec<FileInfo>>,
+    hashes: &RefCell<FileHashes>,
+    cancellation: &Cancellation,
+) -> io::Result<Vec<Vec<FileInfo>>> {
+    let members: Vec<&mut FileInfo> = groups
+        .iter_mut()
+        .flatten()
+        .filter(|fi| fi.is_archive_member() && cached_hash(fi, hashes).is_none())
+        .collect();
+    let unreadable = archives::hash_members(members, cancellation)?;
+    if !unreadable.is_empty() {
+        for group in &mut groups {
+            group.retain(|fi| !unreadable.contains(&fi.path));
+        }
+        groups.retain(|group| group.len() >= 2);
+    }
+    Ok(groups)
 }
 
 fn split(
@@ -157,27 +591,151 @@ fn split(
 }
 
 fn find_duplicates(
-    paths: &[PathBuf],
-    exclude: &[glob::Pattern],
-    min_size: u64,
-    recurse: bool,
+    files: Vec<FileInfo>,
+    hashes: &RefCell<FileHashes>,
+    cancellation: &Cancellation,
 ) -> io::Result<Vec<Vec<FileInfo>>> {
-    let files = find_files_in_dirs(paths, exclude, min_size, recurse)?;
-
-    let files = unique_by(|fi| Ok((fi.device, fi.inode)), files)?;
-    let files = unique_by(|fi| Ok(fi.path.clone()), files)?;
-
     let mut groups: Vec<Vec<FileInfo>> = vec![files];
     groups = split(groups, group_by_size)?;
     groups = split(groups, group_by_size)?;
-    groups = split(groups, group_by_hash)?;
+    groups = hash_archive_members(groups, hashes, cancellation)?;
+    groups = split(groups, |group| group_by_hash(group, hashes, cancellation))?;
 
     Ok(groups)
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchOptions {
+    pub paths: Vec<PathBuf>,
+    pub exclude: Vec<Exclusion>,
+    pub min_size: u64,
+    /// Files larger than this are not searched.
+    pub max_size: Option<u64>,
+    /// Maximal depth of searched files, 1 means files of searched directories only.
+    pub max_depth: Option<u32>,
+    pub filter: Option<Filter>,
+    /// Skip files listed in .gitignore, .ignore and .dupignore files.
+    pub ignore_files: bool,
+    pub find_directories: bool,
+    /// Minimal similarity (in percents) of directories reported as nearly identical.
+    pub directory_similarity: u8,
+    pub similar_images: bool,
+    pub image_hash: ImageHash,
+    /// Maximal Hamming distance between hashes of similar images.
+    pub image_distance: u32,
+    pub audio_match: AudioMatch,
+    /// Compare files of known formats without their metadata (EXIF, ID3 tags, etc.)
+    pub ignore_metadata: bool,
+    /// Look for duplicates among members of ZIP and TAR archives.
+    pub search_archives: bool,
+    pub symlinks: SymlinkPolicy,
+    /// Do not descend into directories on other file systems (like `find -xdev`).
+    pub one_file_system: bool,
+    /// Types of file systems (as in /proc/self/mountinfo) which are not searched.
+    pub skip_file_systems: Vec<String>,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            paths: Vec::new(),
+            exclude: Vec::new(),
+            min_size: 1,
+            max_size: None,
+            max_depth: None,
+            filter: None,
+            ignore_files: false,
+            find_directories: false,
+            directory_similarity: 90,
+            similar_images: false,
+            image_hash: ImageHash::Difference,
+            image_distance: 8,
+            audio_match: AudioMatch::Exact,
+            ignore_metadata: false,
+            search_archives: false,
+            symlinks: SymlinkPolicy::Ignore,
+            one_file_system: false,
+            skip_file_systems: Vec::new(),
+        }
+    }
+}
+
+impl SearchOptions {
+    fn accepts_size(&self, size: u64) -> bool {
+        size >= self.min_size && self.max_size.is_none_or(|max_size| size <= max_size)
+    }
+
+    fn descends_below(&self, depth: u32) -> bool {
+        self.max_depth.is_none_or(|max_depth| depth < max_depth)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchKind {
+    Exact,
+    SimilarImage,
+    AudioContent,
+    AudioTags,
+    /// Files differ in metadata only.
+    Content,
+}
+
 #[derive(Debug)]
 pub struct DuplicatesGroup {
+    pub kind: MatchKind,
     pub files: Vec<FileInfo>,
+    /// Similarity of files in percents. Exact duplicates have 100.
+    pub similarity: u8,
+    /// Symbolic links to files of the group. They do not take space, so they are not counted.
+    pub links: Vec<FileInfo>,
+}
+
+/// Space taken by files.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Waste {
+    /// Sum of sizes of files.
+    pub logical: u64,
+    /// Sum of allocated blocks. It is less than the size for sparse files and more for small
+    /// ones. Members of archives take no blocks on their own.
+    pub on_disk: u64,
+}
+
+impl Waste {
+    pub fn of_file(fi: &FileInfo) -> Self {
+        Self {
+            logical: fi.size,
+            on_disk: fi.disk_usage,
+        }
+    }
+}
+
+impl std::ops::Add for Waste {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            logical: self.logical + other.logical,
+            on_disk: self.on_disk + other.on_disk,
+        }
+    }
+}
+
+impl std::ops::Sub for Waste {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self {
+            logical: self.logical - other.logical,
+            on_disk: self.on_disk - other.on_disk,
+        }
+    }
+}
+
+impl std::iter::Sum for Waste {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |a, b| a + b)
+    }
 }
 
 impl DuplicatesGroup {
@@ -185,58 +743,390 @@ impl DuplicatesGroup {
         self.files[0].size
     }
 
-    pub fn waste(&self) -> u64 {
-        self.size() * self.files.len() as u64
+    /// Space taken by all files of the group.
+    pub fn total(&self) -> Waste {
+        self.files.iter().map(Waste::of_file).sum()
+    }
+
+    /// Space freed by removing redundant copies, i.e. all files but the largest one, which is
+    /// kept. Files with hard links outside of the search free nothing, so one of them is
+    /// preferred as the kept one.
+    pub fn waste(&self) -> Waste {
+        self.redundant().map(FileInfo::freed).sum()
+    }
+
+    /// Files which are not kept, see `waste`.
+    pub fn redundant(&self) -> impl Iterator<Item = &FileInfo> {
+        let kept = self
+            .files
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, fi)| (fi.size, fi.freed() == Waste::default()))
+            .map(|(index, _)| index);
+        self.files
+            .iter()
+            .enumerate()
+            .filter(move |(index, _)| Some(*index) != kept)
+            .map(|(_, fi)| fi)
     }
 }
 
-fn exclusion_to_pattern(exclusion: &Exclusion) -> Result<glob::Pattern, Box<dyn Error>> {
-    let pattern = match exclusion {
-        Exclusion::Directory(dir) => glob::Pattern::new(
-            dir.to_str()
-                .ok_or_else(|| format!("Cannot create glob pattern from {}.", dir.display()))?,
-        )?,
-        Exclusion::Pattern(pattern) => glob::Pattern::new(pattern)?,
-    };
-    Ok(pattern)
+#[derive(Debug, Default)]
+pub struct SearchResults {
+    pub groups: Vec<DuplicatesGroup>,
+    pub directories: Vec<DirectoryGroup>,
+    /// State to refresh the results with `refresh_duplicate_groups`.
+    pub cache: ScanCache,
+}
+
+impl SearchResults {
+    /// File groups which are not a part of a reported duplicate directory.
+    pub fn uncollapsed_groups(&self) -> impl Iterator<Item = &DuplicatesGroup> {
+        self.groups.iter().filter(move |group| {
+            !self
+                .directories
+                .iter()
+                .any(|directories| directories.covers(group))
+        })
+    }
+
+    /// Files under `dir` whose content has a copy outside of it. Members of archives are left
+    /// out on both sides, as they cannot be removed and are not kept copies of their own.
+    pub fn duplicates_under(&self, dir: &Path) -> Vec<PathBuf> {
+        let is_file = |fi: &&FileInfo| fi.archive_member.is_none();
+        self.groups
+            .iter()
+            .filter(|group| group.kind == MatchKind::Exact)
+            .filter(|group| {
+                group
+                    .files
+                    .iter()
+                    .filter(is_file)
+                    .any(|fi| !fi.path.starts_with(dir))
+            })
+            .flat_map(|group| group.files.iter().filter(is_file))
+            .filter(|fi| fi.path.starts_with(dir))
+            .map(|fi| fi.path.clone())
+            .collect()
+    }
+
+    /// Removes files at `path` or under it, including members of archives there, and groups
+    /// which are left with one file or none.
+    pub fn remove_under(&mut self, path: &Path) {
+        let removed = |fi: &FileInfo| {
+            fi.path.starts_with(path)
+                || fi
+                    .archive_member
+                    .as_ref()
+                    .is_some_and(|member| member.archive.starts_with(path))
+        };
+        for group in &mut self.groups {
+            group.files.retain(|fi| !removed(fi));
+            group.links.retain(|link| !link.path.starts_with(path));
+            for fi in &mut group.files {
+                fi.hard_links.retain(|link| !link.starts_with(path));
+            }
+        }
+        self.groups.retain(|group| group.files.len() >= 2);
+        for group in &mut self.directories {
+            group.directories.retain(|dir| !dir.path.starts_with(path));
+        }
+        self.directories
+            .retain(|group| group.directories.len() >= 2);
+    }
+
+    /// Stops reporting `dir` as a duplicate directory, its files are kept.
+    pub fn remove_directory(&mut self, dir: &Path) {
+        for group in &mut self.directories {
+            group.directories.retain(|info| info.path != dir);
+        }
+        self.directories
+            .retain(|group| group.directories.len() >= 2);
+    }
+
+    /// Moves files at `from` or under it to `to`.
+    pub fn rename(&mut self, from: &Path, to: &Path) {
+        let rename = |path: &mut PathBuf| {
+            if let Ok(rest) = path.strip_prefix(from) {
+                *path = to.join(rest);
+            }
+        };
+        for group in &mut self.groups {
+            for fi in group.files.iter_mut().chain(&mut group.links) {
+                rename(&mut fi.path);
+                if let Some(member) = &mut fi.archive_member {
+                    rename(&mut member.archive);
+                }
+                for link in &mut fi.hard_links {
+                    rename(link);
+                }
+            }
+        }
+        for group in &mut self.directories {
+            for dir in &mut group.directories {
+                rename(&mut dir.path);
+            }
+        }
+    }
 }
 
+/// Searches for duplicates. The search stops with an error once `cancellation` is cancelled.
 pub fn find_duplicate_groups(
-    paths: &[PathBuf],
-    exclude: &[Exclusion],
-    min_size: u64,
-    recurse: bool,
-) -> Result<Vec<DuplicatesGroup>, Box<dyn Error>> {
-    let exclude: Vec<glob::Pattern> = exclude
-        .iter()
-        .map(exclusion_to_pattern)
+    options: &SearchOptions,
+    cancellation: &Cancellation,
+) -> Result<SearchResults, Box<dyn Error>> {
+    search(options, ScanCache::default(), cancellation)
+}
+
+/// Repeats a search with the cache of its results. Only directories modified since the search
+/// are read, and only files with another size or modification time are hashed again. Similar
+/// images, audio and normalized content are compared anew.
+pub fn refresh_duplicate_groups(
+    options: &SearchOptions,
+    cache: ScanCache,
+    cancellation: &Cancellation,
+) -> Result<SearchResults, Box<dyn Error>> {
+    search(options, cache, cancellation)
+}
+
+fn search(
+    options: &SearchOptions,
+    cache: ScanCache,
+    cancellation: &Cancellation,
+) -> Result<SearchResults, Box<dyn Error>> {
+    let (include, exclude): (Vec<&Exclusion>, Vec<&Exclusion>) =
+        options.exclude.iter().partition(|e| e.is_inclusion());
+    let include: Vec<ExclusionMatcher> = include
+        .into_iter()
+        .map(Exclusion::matcher)
+        .collect::<Result<_, _>>()?;
+    let exclude: Vec<ExclusionMatcher> = exclude
+        .into_iter()
+        .map(Exclusion::matcher)
         .collect::<Result<_, _>>()?;
 
-    let duplicates1 = find_duplicates(&paths, &exclude, min_size, recurse)?;
+    let (files, walked_directories) =
+        find_files_in_dirs(options, &exclude, &include, cache.directories, cancellation)?;
+    let ScanCache {
+        mut hashes,
+        mut image_hashes,
+        mut fingerprints,
+        mut tags,
+        mut normalized,
+        ..
+    } = cache;
+    let walked: HashSet<&Path> = files.iter().map(|fi| fi.path.as_path()).collect();
+    hashes.retain(&walked);
+    image_hashes.retain(&walked);
+    fingerprints.retain(&walked);
+    tags.retain(&walked);
+    normalized.retain(&walked);
+    let hashes = RefCell::new(hashes);
+
+    let scanned: Vec<(PathBuf, Waste)> = if options.find_directories {
+        files
+            .iter()
+            .filter(|fi| !fi.is_archive_member() && !fi.is_symlink())
+            .map(|fi| (fi.path.clone(), Waste::of_file(fi)))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let (links, files): (Vec<FileInfo>, Vec<FileInfo>) =
+        files.into_iter().partition(FileInfo::is_symlink);
+    let mut files = merge_hard_links(files);
+
+    // A followed link is compared only if its target was not found by itself, other links are
+    // shown along with the target.
+    let mut known: HashSet<_> = files.iter().map(FileInfo::identity).collect();
+    let mut links = unique_by(|fi| Ok(fi.path.clone()), links)?;
+    if options.symlinks == SymlinkPolicy::Follow {
+        links.retain(|link| {
+            if known.insert(link.identity()) {
+                files.push(link.clone());
+                false
+            } else {
+                true
+            }
+        });
+    }
+    let files = unique_by(|fi| Ok(fi.path.clone()), files)?;
+
+    let images: Vec<FileInfo> = if options.similar_images {
+        files
+            .iter()
+            .filter(|fi| !fi.is_archive_member() && is_image(fi))
+            .cloned()
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let tracks: Vec<FileInfo> = if options.audio_match != AudioMatch::Exact {
+        files
+            .iter()
+            .filter(|fi| !fi.is_archive_member() && is_audio(fi))
+            .cloned()
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let normalizers = if options.ignore_metadata {
+        normalizers()
+    } else {
+        Vec::new()
+    };
+    let normalizable: Vec<FileInfo> = files
+        .iter()
+        .filter(|fi| !fi.is_archive_member() && normalizers.iter().any(|n| n.accepts(fi)))
+        .cloned()
+        .collect();
+
+    let duplicates1 = find_duplicates(files, &hashes, cancellation)?;
 
     let mut duplicates: Vec<DuplicatesGroup> = vec![];
     for dup in duplicates1 {
-        duplicates.push(DuplicatesGroup { files: dup });
+        duplicates.push(DuplicatesGroup {
+            kind: MatchKind::Exact,
+            files: dup,
+            similarity: 100,
+            links: Vec::new(),
+        });
     }
 
-    duplicates.sort_by_key(|group| group.waste());
+    // Exact copies are already grouped. They are not compared by content, so no file is
+    // reported in two groups and counted twice.
+    let copies: HashSet<PathBuf> = duplicates
+        .iter()
+        .flat_map(|group| group.files.iter().map(|fi| fi.path.clone()))
+        .collect();
+
+    let images: Vec<FileInfo> = images
+        .into_iter()
+        .filter(|fi| !copies.contains(&fi.path))
+        .collect();
+    let similar = group_similar_images(
+        images,
+        options.image_hash,
+        options.image_distance,
+        &mut image_hashes,
+        cancellation,
+    )?;
+    for (dup, distance) in similar {
+        duplicates.push(DuplicatesGroup {
+            kind: MatchKind::SimilarImage,
+            files: dup,
+            similarity: distance_to_similarity(distance),
+            links: Vec::new(),
+        });
+    }
+
+    let tracks: Vec<FileInfo> = tracks
+        .into_iter()
+        .filter(|fi| !copies.contains(&fi.path))
+        .collect();
+    let (kind, same_tracks) = match options.audio_match {
+        AudioMatch::Exact => (MatchKind::Exact, Vec::new()),
+        AudioMatch::Content => (
+            MatchKind::AudioContent,
+            audio::group_by_content(tracks, &mut fingerprints, cancellation)?,
+        ),
+        AudioMatch::Tags => (
+            MatchKind::AudioTags,
+            audio::group_by_tags(tracks, &mut tags, cancellation)?,
+        ),
+    };
+    for dup in same_tracks {
+        duplicates.push(DuplicatesGroup {
+            kind,
+            files: dup,
+            similarity: 100,
+            links: Vec::new(),
+        });
+    }
+
+    // Files matched by images or audio are not compared again.
+    let matched: HashSet<PathBuf> = duplicates
+        .iter()
+        .filter(|group| group.kind != MatchKind::Exact)
+        .flat_map(|group| group.files.iter().map(|fi| fi.path.clone()))
+        .collect();
+    let normalizable: Vec<FileInfo> = normalizable
+        .into_iter()
+        .filter(|fi| !copies.contains(&fi.path) && !matched.contains(&fi.path))
+        .collect();
+    let same_content =
+        normalizers::group_by_content(&normalizers, normalizable, &mut normalized, cancellation)?;
+    for dup in same_content {
+        duplicates.push(DuplicatesGroup {
+            kind: MatchKind::Content,
+            files: dup,
+            similarity: 100,
+            links: Vec::new(),
+        });
+    }
+
+    let mut group_of: HashMap<_, usize> = HashMap::new();
+    for (index, group) in duplicates.iter().enumerate() {
+        for fi in &group.files {
+            group_of.entry(fi.identity()).or_insert(index);
+        }
+    }
+    for link in links {
+        if let Some(index) = group_of.get(&link.identity()) {
+            duplicates[*index].links.push(link);
+        }
+    }
+
+    duplicates.sort_by_key(|group| group.waste().logical);
     duplicates.reverse();
 
-    Ok(duplicates)
+    let directories = if options.find_directories {
+        find_duplicate_directories(
+            &options.paths,
+            &scanned,
+            &duplicates,
+            options.directory_similarity,
+        )
+    } else {
+        Vec::new()
+    };
+
+    Ok(SearchResults {
+        groups: duplicates,
+        directories,
+        cache: ScanCache {
+            directories: walked_directories,
+            hashes: hashes.into_inner(),
+            image_hashes,
+            fingerprints,
+            tags,
+            normalized,
+        },
+    })
 }
 
-pub fn duplication_status(dups: &[DuplicatesGroup]) -> String {
-    let mut waste_bytes = 0;
+pub fn duplication_status(results: &SearchResults) -> String {
+    let dups = &results.groups;
+    let mut waste = Waste::default();
     let mut waste_count = 0;
     for dup in dups {
-        waste_bytes += dup.waste();
+        waste = waste + dup.waste();
         waste_count += dup.files.len() - 1;
     }
 
-    format!(
-        "{} wasted in {} files (in {} groups)",
-        format_size(waste_bytes, DECIMAL),
+    let mut status = format!(
+        "{} wasted ({} on disk) in {} files (in {} groups)",
+        format_size(waste.logical, DECIMAL),
+        format_size(waste.on_disk, DECIMAL),
         waste_count,
         dups.len()
-    )
+    );
+    if !results.directories.is_empty() {
+        status.push_str(&format!(
+            "\n{} groups of duplicate directories",
+            results.directories.len()
+        ));
+    }
+    status
 }
@@ -0,0 +1,161 @@
+use ignore::gitignore::Gitignore;
+use ignore::Match;
+use std::path::Path;
+
+/// Ignore files in order of increasing precedence.
+const IGNORE_FILES: [&str; 3] = [".gitignore", ".ignore", ".dupignore"];
+
+/// Ignore files of directories from a searched directory down to the walked one. Patterns of
+/// deeper directories override patterns of their parents, as in git.
+pub struct IgnoreStack {
+    /// Matchers per directory, outermost directory first. Matchers of a directory are ordered
+    /// by decreasing precedence.
+    levels: Vec<Vec<Gitignore>>,
+}
+
+fn load(dir: &Path) -> Vec<Gitignore> {
+    IGNORE_FILES
+        .iter()
+        .rev()
+        .map(|name| dir.join(name))
+        .filter(|path| path.is_file())
+        // malformed lines are skipped, the rest of a file is still used
+        .map(|path| Gitignore::new(path).0)
+        .collect()
+}
+
+impl IgnoreStack {
+    /// Starts with ignore files of parents of `root` up to the root of the git repository it is
+    /// in, so a subdirectory of a project is searched with rules of the project. Outside of a
+    /// repository ignore files of parents are not used.
+    pub fn new(root: &Path) -> Self {
+        let parents = root.ancestors().skip(1);
+        let repository = root.ancestors().position(|dir| dir.join(".git").exists());
+        let mut levels: Vec<Vec<Gitignore>> = match repository {
+            Some(depth) => parents.take(depth).map(load).collect(),
+            None => Vec::new(),
+        };
+        levels.reverse();
+        Self { levels }
+    }
+
+    pub fn push(&mut self, dir: &Path) {
+        self.levels.push(load(dir));
+    }
+
+    pub fn pop(&mut self) {
+        self.levels.pop();
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        for matcher in self.levels.iter().rev().flatten() {
+            match matcher.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Temporary directory with files, removed on drop.
+    struct Tree(PathBuf);
+
+    impl Tree {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let root = std::env::temp_dir().join(format!(
+                "find-duplicates-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&root);
+            for (path, content) in files {
+                let path = root.join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, content).unwrap();
+            }
+            Self(root)
+        }
+    }
+
+    impl Drop for Tree {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn precedence_of_ignore_files() {
+        let tree = Tree::new(
+            "precedence",
+            &[
+                (".gitignore", "*.log\n*.tmp\n"),
+                (".ignore", "!keep.log\n!*.tmp\n"),
+                (".dupignore", "a.tmp\n"),
+            ],
+        );
+        let mut stack = IgnoreStack::new(&tree.0);
+        stack.push(&tree.0);
+
+        assert!(stack.is_ignored(&tree.0.join("other.log"), false));
+        assert!(!stack.is_ignored(&tree.0.join("keep.log"), false));
+        assert!(stack.is_ignored(&tree.0.join("a.tmp"), false));
+        assert!(!stack.is_ignored(&tree.0.join("b.tmp"), false));
+        assert!(!stack.is_ignored(&tree.0.join("c.txt"), false));
+    }
+
+    #[test]
+    fn deeper_directories_override_parents() {
+        let tree = Tree::new(
+            "levels",
+            &[
+                (".gitignore", "*.log\nbuild/\n"),
+                ("sub/.gitignore", "!other.log\n"),
+            ],
+        );
+        let sub = tree.0.join("sub");
+        let mut stack = IgnoreStack::new(&tree.0);
+        stack.push(&tree.0);
+        assert!(stack.is_ignored(&tree.0.join("build"), true));
+        assert!(!stack.is_ignored(&tree.0.join("build"), false));
+
+        stack.push(&sub);
+        assert!(!stack.is_ignored(&sub.join("other.log"), false));
+        assert!(stack.is_ignored(&sub.join("debug.log"), false));
+
+        stack.pop();
+        assert!(stack.is_ignored(&sub.join("other.log"), false));
+    }
+
+    #[test]
+    fn parents_are_loaded_up_to_repository_root() {
+        let tree = Tree::new(
+            "repository",
+            &[
+                (".gitignore", "*.txt\n"),
+                ("repo/.git/HEAD", ""),
+                ("repo/.gitignore", "*.log\n"),
+                ("repo/sub/.ignore", ""),
+                ("plain/sub/.ignore", ""),
+            ],
+        );
+        let sub = tree.0.join("repo/sub");
+        let mut stack = IgnoreStack::new(&sub);
+        stack.push(&sub);
+        assert!(stack.is_ignored(&sub.join("a.log"), false));
+        assert!(!stack.is_ignored(&sub.join("a.txt"), false));
+
+        // outside of a repository ignore files of parents are not used
+        let sub = tree.0.join("plain/sub");
+        let mut stack = IgnoreStack::new(&sub);
+        stack.push(&sub);
+        assert!(!stack.is_ignored(&sub.join("a.txt"), false));
+    }
+}
@@ -1,12 +1,28 @@
 mod application;
+mod archives;
+mod audio;
+mod batch;
+mod dbus;
+mod duplicate_directories;
 mod duplicates_list;
 mod exclusion;
+mod filter;
 mod find_duplicates;
 mod gtk_prelude;
+mod ignore_files;
 mod main_window;
+mod mounts;
+mod normalizers;
 mod options;
 mod path_choose;
+mod preferences;
+mod profiles;
+mod scheduler;
+mod similar_images;
+mod size_range;
+mod statistics;
 mod string_list;
+mod treemap;
 mod user_interaction;
 mod utils;
 mod widgets;
@@ -1,15 +1,26 @@
-use crate::duplicates_list;
-use crate::exclusion::{Exclusion, DEFAULT_EXCLUDE_PATTERNS};
-use crate::find_duplicates::{duplication_status, find_duplicate_groups, DuplicatesGroup};
+use crate::duplicates_list::{self, DuplicateFile};
+use crate::find_duplicates::{
+    duplication_status, find_duplicate_groups, refresh_duplicate_groups, Cancellation,
+    DuplicatesGroup, MatchKind, ScanCache, SearchOptions, SearchResults,
+};
 use crate::gtk_prelude::*;
 use crate::options;
 use crate::path_choose;
+use crate::preferences::{default_search_options, Preferences};
+use crate::profiles;
+use crate::scheduler::{self, Interval, Schedule};
+use crate::size_range::parse_bytes;
+use crate::statistics::{contains_under, Statistics, CHART_TITLES};
+use crate::treemap;
 use crate::user_interaction::{self, ProgressDialog};
-use crate::utils::horizontal_expander;
+use crate::utils::{horizontal_expander, scrolled};
+use crate::widgets::bar_chart::BarChart;
 use crate::widgets::go_button::go_button;
 use crate::widgets::menu_builder::MenuBuilderExt;
+use crate::widgets::treemap_view::TreemapView;
 use gtk::subclass::prelude::*;
 use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashSet};
 use std::env;
 use std::error::Error;
 use std::fs;
@@ -18,6 +29,9 @@ use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::thread;
 
+/// Most directories watched at once. Every one takes an inotify watch and they are limited.
+const MAX_MONITORS: usize = 1000;
+
 fn xdg_open(file: &Path) -> Result<(), Box<dyn Error>> {
     Command::new("xdg-open").arg(file).spawn()?;
     Ok(())
@@ -88,17 +102,84 @@ fn sidebar_layout(child: &gtk::Widget, action: &gtk::Button) -> gtk::Widget {
     grid.upcast()
 }
 
-fn results_layout(top: &gtk::Widget, bottom: &gtk::Widget) -> gtk::Widget {
+fn results_layout(filter: &gtk::Widget, top: &gtk::Widget, bottom: &gtk::Widget) -> gtk::Widget {
     let bx = gtk::Box::builder()
         .orientation(gtk::Orientation::Vertical)
         .homogeneous(false)
         .build();
+    bx.append(filter);
     bx.append(top);
     bx.append(bottom);
     bx.upcast()
 }
 
-fn panes(sidebar: &gtk::Widget, main: &gtk::Widget) -> gtk::Widget {
+fn filter_bar(label: &gtk::Label) -> gtk::Widget {
+    let row = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .spacing(8)
+        .margin_start(8)
+        .margin_end(8)
+        .margin_top(8)
+        .margin_bottom(8)
+        .visible(false)
+        .build();
+    label.set_hexpand(true);
+    label.set_xalign(0.0);
+    label.set_ellipsize(pango::EllipsizeMode::Middle);
+    row.append(label);
+    row.append(
+        &gtk::Button::builder()
+            .label("Show all")
+            .action_name("win.clear_filter")
+            .build(),
+    );
+    row.upcast()
+}
+
+fn statistics_layout(charts: &[BarChart]) -> gtk::Widget {
+    let grid = gtk::Grid::builder()
+        .column_homogeneous(true)
+        .row_spacing(16)
+        .column_spacing(16)
+        .margin_start(8)
+        .margin_end(8)
+        .margin_top(8)
+        .margin_bottom(8)
+        .build();
+    for (index, (chart, title)) in charts.iter().zip(CHART_TITLES).enumerate() {
+        let frame = gtk::Frame::builder()
+            .label(title)
+            .child(&chart.get_widget())
+            .build();
+        grid.attach(&frame, index as i32 % 2, index as i32 / 2, 1, 1);
+    }
+    scrolled(&grid, false).upcast()
+}
+
+fn results_stack(
+    duplicates: &gtk::Widget,
+    statistics: &gtk::Widget,
+    treemap: &gtk::Widget,
+) -> (gtk::Widget, gtk::Stack) {
+    let stack = gtk::Stack::new();
+    stack.add_titled(duplicates, Some("duplicates"), "Duplicates");
+    stack.add_titled(statistics, Some("statistics"), "Statistics");
+    stack.add_titled(treemap, Some("treemap"), "Treemap");
+    let switcher = gtk::StackSwitcher::builder()
+        .stack(&stack)
+        .halign(gtk::Align::Center)
+        .margin_top(8)
+        .build();
+    let bx = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .homogeneous(false)
+        .build();
+    bx.append(&switcher);
+    bx.append(&stack);
+    (bx.upcast(), stack)
+}
+
+fn panes(sidebar: &gtk::Widget, main: &gtk::Widget) -> gtk::Paned {
     gtk::Paned::builder()
         .start_child(sidebar)
         .end_child(main)
@@ -107,9 +188,36 @@ fn panes(sidebar: &gtk::Widget, main: &gtk::Widget) -> gtk::Widget {
         .resize_end_child(true)
         .shrink_end_child(false)
         .build()
+}
+
+fn main_menu(
+    profiles: &gio::Menu,
+    schedule_profiles: &gio::Menu,
+    delete_profiles: &gio::Menu,
+) -> gtk::Widget {
+    let menu = gio::Menu::new()
+        .submenu("Profiles", profiles.clone())
+        .item("Save as profile...", "win.save_profile")
+        .submenu("Schedule profile", schedule_profiles.clone())
+        .submenu("Delete profile", delete_profiles.clone())
+        .item("Reset to defaults", "win.reset_options");
+    gtk::MenuButton::builder()
+        .icon_name("open-menu-symbolic")
+        .menu_model(&menu)
+        .build()
         .upcast()
 }
 
+/// Fills the menu with an item per profile which activates the action with the profile name.
+fn fill_profiles_menu(menu: &gio::Menu, names: &[String], action: &str) {
+    menu.remove_all();
+    for name in names {
+        let item = gio::MenuItem::new(Some(name), None);
+        item.set_action_and_target_value(Some(action), Some(&name.to_variant()));
+        menu.append_item(&item);
+    }
+}
+
 fn duplicates_popup() -> gio::Menu {
     gio::Menu::new()
         .item("Open", "win.open")
@@ -122,7 +230,55 @@ fn duplicates_popup() -> gio::Menu {
         )
 }
 
-type FindResult = Result<Vec<DuplicatesGroup>, String>;
+fn matched_group_label(group: &DuplicatesGroup) -> String {
+    let count = group.files.len();
+    match group.kind {
+        MatchKind::Exact => format!("{} x {}", count, group.size()),
+        MatchKind::SimilarImage => {
+            format!("{} similar images ({}% similar)", count, group.similarity)
+        }
+        MatchKind::AudioContent => format!("{} tracks with the same audio", count),
+        MatchKind::AudioTags => format!("{} tracks with the same tags", count),
+        MatchKind::Content => format!("{} files, content match", count),
+    }
+}
+
+/// Results of a search along with its options.
+type FindResult = Result<(SearchOptions, SearchResults), String>;
+
+fn append_duplicates_group(store: &duplicates_list::DuplicatesStore, group: &DuplicatesGroup) {
+    if group.kind == MatchKind::Exact {
+        store.append_group(group.files.len(), group.size(), group.waste());
+    } else {
+        store.append_matched_group(
+            &matched_group_label(group),
+            group.waste(),
+            group.total().logical,
+        );
+    }
+    for fi in &group.files {
+        let file = if group.kind == MatchKind::SimilarImage {
+            store.append_image(&fi.path, fi.modified, fi.size)
+        } else {
+            store.append_file(&fi.path, fi.modified, fi.size)
+        };
+        if let Some(member) = &fi.archive_member {
+            store.set_archive(&file, &member.archive);
+        }
+        if let Some(target) = &fi.link_target {
+            store.set_link_target(&file, target);
+        }
+        if fi.nlink > 1 {
+            store.set_hard_links(&file, fi.nlink, &fi.hard_links);
+        }
+    }
+    for link in &group.links {
+        let file = store.append_file(&link.path, link.modified, link.size);
+        if let Some(target) = &link.link_target {
+            store.set_link_target(&file, target);
+        }
+    }
+}
 
 mod imp {
     use super::*;
@@ -131,11 +287,37 @@ mod imp {
     #[derive(Default)]
     pub struct MainWindow {
         pub confirm_delete: Cell<bool>,
+        /// Saved preferences. Search options are kept if the form is invalid on close.
+        pub preferences: RefCell<Preferences>,
+        pub paned: OnceCell<gtk::Paned>,
+        pub title: OnceCell<gtk::Label>,
+        pub profiles_menu: OnceCell<gio::Menu>,
+        pub schedule_profiles_menu: OnceCell<gio::Menu>,
+        pub delete_profiles_menu: OnceCell<gio::Menu>,
+        /// Name of the loaded or last saved profile.
+        pub profile: RefCell<Option<String>>,
         pub duplicates: duplicates_list::DuplicatesStore,
         pub options: options::Options,
         pub view: duplicates_list::DuplicatesList,
         pub find_sender: OnceCell<glib::Sender<FindResult>>,
         pub progress: RefCell<Option<ProgressDialog>>,
+        /// Cancellation of the running search.
+        pub search: RefCell<Option<Cancellation>>,
+        /// Outcome of the last search.
+        pub status: RefCell<Option<String>>,
+        /// Options and cache of the last successful search, to refresh its results.
+        pub last_search: RefCell<Option<(SearchOptions, ScanCache)>>,
+        pub watch: Cell<bool>,
+        /// Monitors of searched directories while watching.
+        pub monitors: RefCell<Vec<gio::FileMonitor>>,
+        pub stack: OnceCell<gtk::Stack>,
+        pub charts: OnceCell<Vec<BarChart>>,
+        pub treemap: OnceCell<TreemapView>,
+        pub filter_bar: OnceCell<gtk::Widget>,
+        pub filter_label: gtk::Label,
+        /// Groups of the last search, to filter them by bars of charts. The cache is not kept.
+        pub results: RefCell<SearchResults>,
+        pub statistics: RefCell<Option<Statistics>>,
     }
 
     #[glib::object_subclass]
@@ -149,9 +331,13 @@ mod imp {
         fn constructed(&self) {
             self.parent_constructed();
 
+            let preferences = Preferences::load();
+
             let window = self.obj();
-            window.set_default_width(1200);
-            window.set_default_height(800);
+            window.set_default_size(preferences.window_width, preferences.window_height);
+            if preferences.maximized {
+                window.maximize();
+            }
             window.set_resizable(true);
 
             let title_label = gtk::Label::builder()
@@ -165,6 +351,37 @@ mod imp {
                 .show_title_buttons(true)
                 .title_widget(&title_label)
                 .build();
+            let profiles_menu = gio::Menu::new();
+            let schedule_profiles_menu = gio::Menu::new();
+            let delete_profiles_menu = gio::Menu::new();
+            headerbar.pack_end(&main_menu(
+                &profiles_menu,
+                &schedule_profiles_menu,
+                &delete_profiles_menu,
+            ));
+            let refresh = gtk::Button::builder()
+                .icon_name("view-refresh-symbolic")
+                .tooltip_text("Refresh results")
+                .action_name("win.refresh")
+                .build();
+            headerbar.pack_start(&refresh);
+            let watch = gtk::ToggleButton::builder()
+                .label("Watch")
+                .tooltip_text("Update results when files change")
+                .active(preferences.watch)
+                .build();
+            watch.connect_toggled(clone!(@weak self as imp => move |button| {
+                imp.set_watching(button.is_active());
+            }));
+            headerbar.pack_start(&watch);
+            self.watch.set(preferences.watch);
+            self.title.set(title_label).unwrap();
+            self.profiles_menu.set(profiles_menu).unwrap();
+            self.schedule_profiles_menu
+                .set(schedule_profiles_menu)
+                .unwrap();
+            self.delete_profiles_menu.set(delete_profiles_menu).unwrap();
+            self.refresh_profiles();
             window.set_titlebar(Some(&headerbar));
 
             let menu = duplicates_popup();
@@ -173,13 +390,42 @@ mod imp {
             self.view.set_popup(&menu.upcast());
 
             let action_buttons = action_buttons();
+            let filter_bar = filter_bar(&self.filter_label);
+            let charts: Vec<BarChart> = (0..CHART_TITLES.len())
+                .map(|chart| {
+                    BarChart::new(clone!(@weak self as imp => move |bar| {
+                        imp.filter_by(chart, bar);
+                    }))
+                })
+                .collect();
+            let treemap = TreemapView::new(
+                clone!(@weak self as imp => move |files| imp.select_files(&files)),
+                clone!(@weak self as imp => move || {
+                    if let Some(stack) = imp.stack.get() {
+                        stack.set_visible_child_name("duplicates");
+                    }
+                }),
+            );
+            let (results, stack) = results_stack(
+                &results_layout(&filter_bar, &self.view.get_widget(), &action_buttons),
+                &statistics_layout(&charts),
+                &treemap.get_widget(),
+            );
 
             let paned = panes(
                 &sidebar_layout(&self.options.get_widget(), &go_button("Find", "win.find")),
-                &results_layout(&self.view.get_widget(), &action_buttons),
+                &results,
             );
+            self.stack.set(stack).unwrap();
+            self.charts.set(charts).unwrap();
+            self.treemap.set(treemap).unwrap();
+            self.filter_bar.set(filter_bar).unwrap();
 
+            if let Some(position) = preferences.sidebar_width {
+                paned.set_position(position);
+            }
             window.set_child(Some(&paned));
+            self.paned.set(paned).unwrap();
 
             let (find_sender, find_receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
             self.find_sender.set(find_sender).unwrap();
@@ -195,44 +441,313 @@ mod imp {
                 }),
             );
 
-            for ignore in DEFAULT_EXCLUDE_PATTERNS.iter() {
-                window.add_excluded(ignore.clone());
-            }
-
-            self.confirm_delete.set(true);
+            self.options.set_search_options(&preferences.search);
+            self.confirm_delete.set(preferences.confirm_delete);
+            *self.preferences.borrow_mut() = preferences;
         }
     }
 
     impl WidgetImpl for MainWindow {}
-    impl WindowImpl for MainWindow {}
+
+    impl WindowImpl for MainWindow {
+        fn close_request(&self) -> glib::signal::Inhibit {
+            let preferences = self.current_preferences();
+            if let Err(error) = preferences.save() {
+                eprintln!("Cannot save preferences. {}", error);
+            }
+            self.parent_close_request()
+        }
+    }
+
     impl ApplicationWindowImpl for MainWindow {}
 
     impl MainWindow {
-        async fn on_find_finished(&self, msg: FindResult) {
+        fn current_preferences(&self) -> Preferences {
+            let window = self.obj();
+            let mut preferences = self.preferences.borrow().clone();
+            if let Ok(search) = self.options.get_search_options() {
+                preferences.search = search;
+            }
+            preferences.confirm_delete = self.confirm_delete.get();
+            preferences.watch = self.watch.get();
+            preferences.maximized = window.is_maximized();
+            // size of a maximized window is not the one to restore
+            if !preferences.maximized {
+                let (width, height) = window.default_size();
+                preferences.window_width = width;
+                preferences.window_height = height;
+            }
+            preferences.sidebar_width = self.paned.get().map(|paned| paned.position());
+            preferences
+        }
+
+        pub fn refresh_profiles(&self) {
+            let names = profiles::list();
+            if let Some(menu) = self.profiles_menu.get() {
+                fill_profiles_menu(menu, &names, "win.load_profile");
+            }
+            if let Some(menu) = self.schedule_profiles_menu.get() {
+                fill_profiles_menu(menu, &names, "win.schedule_profile");
+            }
+            if let Some(menu) = self.delete_profiles_menu.get() {
+                fill_profiles_menu(menu, &names, "win.delete_profile");
+            }
+        }
+
+        pub fn set_profile(&self, profile: Option<String>) {
+            if let Some(title) = self.title.get() {
+                match &profile {
+                    Some(name) => title.set_label(&format!("Find duplicates — {}", name)),
+                    None => title.set_label("Find duplicates"),
+                }
+            }
+            *self.profile.borrow_mut() = profile;
+        }
+
+        fn set_watching(&self, watch: bool) {
+            self.watch.set(watch);
+            self.update_monitors();
+        }
+
+        /// Watches directories of files in results and directories above them up to searched
+        /// paths, at most `MAX_MONITORS` of them. Previous monitors are dropped.
+        fn update_monitors(&self) {
+            let mut monitors = self.monitors.borrow_mut();
+            for monitor in monitors.drain(..) {
+                monitor.cancel();
+            }
+            if !self.watch.get() {
+                return;
+            }
+            let last_search = self.last_search.borrow();
+            let Some((options, _)) = &*last_search else {
+                return;
+            };
+            let mut directories = BTreeSet::new();
+            for file in self.duplicates.iter() {
+                let path = self
+                    .duplicates
+                    .get_archive(&file)
+                    .or_else(|| self.duplicates.get_fs_path(&file));
+                let Some(path) = path else {
+                    continue;
+                };
+                for dir in path.ancestors().skip(1) {
+                    if !directories.insert(dir.to_path_buf())
+                        || options.paths.iter().any(|p| p == dir)
+                    {
+                        break;
+                    }
+                }
+            }
+            if directories.len() > MAX_MONITORS {
+                eprintln!(
+                    "Only {} of {} directories are watched.",
+                    MAX_MONITORS,
+                    directories.len()
+                );
+            }
+            // parents come first, so they are watched when there are too many directories
+            for dir in directories.iter().take(MAX_MONITORS) {
+                let monitor = gio::File::for_path(dir)
+                    .monitor_directory(gio::FileMonitorFlags::WATCH_MOVES, gio::Cancellable::NONE);
+                match monitor {
+                    Ok(monitor) => {
+                        monitor.connect_changed(
+                            clone!(@weak self as imp => move |_monitor, file, other_file, event| {
+                                imp.on_file_changed(file, other_file, event);
+                            }),
+                        );
+                        monitors.push(monitor);
+                    }
+                    Err(error) => {
+                        // other directories would fail the same way once watches run out
+                        eprintln!("Cannot watch {}. {}", dir.display(), error);
+                        break;
+                    }
+                }
+            }
+        }
+
+        /// Removed and renamed files are updated in results. Groups of modified files are marked
+        /// as changed, because only a refresh can tell if they are still the same.
+        fn on_file_changed(
+            &self,
+            file: &gio::File,
+            other_file: Option<&gio::File>,
+            event: gio::FileMonitorEvent,
+        ) {
+            let Some(path) = file.path() else {
+                return;
+            };
+            let new_path = other_file.and_then(|other| other.path());
+            match (event, new_path) {
+                (
+                    gio::FileMonitorEvent::Renamed | gio::FileMonitorEvent::MovedOut,
+                    Some(new_path),
+                ) => {
+                    self.results.borrow_mut().rename(&path, &new_path);
+                    self.show_statistics();
+                    for file in self.duplicates.find_under(&path) {
+                        if let Some(archive) = self.duplicates.get_archive(&file) {
+                            if let Ok(rest) = archive.strip_prefix(&path) {
+                                self.duplicates.set_archive(&file, &new_path.join(rest));
+                            }
+                        }
+                        if let Some(old) = self.duplicates.get_fs_path(&file) {
+                            if let Ok(rest) = old.strip_prefix(&path) {
+                                self.duplicates.set_path(&file, &new_path.join(rest));
+                            }
+                        }
+                    }
+                }
+                (gio::FileMonitorEvent::Deleted | gio::FileMonitorEvent::MovedOut, _) => {
+                    let removed = self.duplicates.find_under(&path);
+                    if !removed.is_empty() {
+                        self.duplicates.remove_all(&removed);
+                        self.results.borrow_mut().remove_under(&path);
+                        self.show_statistics();
+                    }
+                }
+                (gio::FileMonitorEvent::Changed | gio::FileMonitorEvent::ChangesDoneHint, _) => {
+                    for file in self.duplicates.find_under(&path) {
+                        self.duplicates.mark_changed(&file);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        pub async fn on_find_finished(&self, msg: FindResult) {
             if let Some(progress) = self.progress.borrow_mut().take() {
                 progress.close().await;
             }
+            let cancelled = self
+                .search
+                .take()
+                .is_some_and(|cancellation| cancellation.is_cancelled());
 
             match msg {
-                Ok(duplicates) => {
-                    for group in &duplicates {
-                        self.duplicates
-                            .append_group(group.files.len(), group.size());
-                        for fi in &group.files {
-                            self.duplicates.append_file(&fi.path, fi.modified, fi.size);
+                Ok((options, mut duplicates)) => {
+                    let cache = std::mem::take(&mut duplicates.cache);
+                    *self.last_search.borrow_mut() = Some((options, cache));
+
+                    // Rows are updated in place, so a refresh keeps selection.
+                    let store = duplicates_list::DuplicatesStore::default();
+                    for group in &duplicates.directories {
+                        store.append_directory_group(
+                            group.directories.len(),
+                            group.size(),
+                            group.similarity,
+                            group.waste(),
+                        );
+                        for dir in &group.directories {
+                            store.append_directory(&dir.path, dir.modified, dir.size, dir.files);
                         }
                     }
+                    for group in duplicates.uncollapsed_groups() {
+                        append_duplicates_group(&store, group);
+                    }
+                    self.duplicates.update(&store);
+                    self.update_monitors();
+
+                    self.clear_filter();
 
                     let status = duplication_status(&duplicates);
+                    *self.status.borrow_mut() = Some(status.clone());
+                    *self.results.borrow_mut() = duplicates;
+                    self.show_statistics();
 
                     user_interaction::notify_info(self.obj().upcast_ref(), &status).await;
                 }
+                Err(_) if cancelled => {
+                    *self.status.borrow_mut() = Some("Search is cancelled.".into());
+                }
                 Err(error) => {
+                    *self.status.borrow_mut() = Some(error.clone());
                     self.show_error(&error).await;
                 }
             }
         }
 
+        /// Shows charts and the treemap of `results`.
+        pub fn show_statistics(&self) {
+            let roots = self
+                .last_search
+                .borrow()
+                .as_ref()
+                .map(|(options, _)| options.paths.clone())
+                .unwrap_or_default();
+            let results = self.results.borrow();
+            let statistics = Statistics::new(&results, &roots);
+            if let Some(charts) = self.charts.get() {
+                for (chart, data) in charts.iter().zip(&statistics.charts) {
+                    chart.set_bars(
+                        data.bars
+                            .iter()
+                            .map(|bar| (bar.label.clone(), bar.waste))
+                            .collect(),
+                    );
+                }
+            }
+            *self.statistics.borrow_mut() = Some(statistics);
+            if let Some(view) = self.treemap.get() {
+                view.set_nodes(treemap::build(&results, &roots));
+            }
+        }
+
+        /// Shows only groups with files of the bar of the chart.
+        fn filter_by(&self, chart: usize, bar: usize) {
+            let statistics = self.statistics.borrow();
+            let Some(statistics) = &*statistics else {
+                return;
+            };
+            let Some(chart) = statistics.charts.get(chart) else {
+                return;
+            };
+            let Some(bar) = chart.bars.get(bar) else {
+                return;
+            };
+            let paths = statistics.matching_paths(&self.results.borrow(), &bar.category);
+            self.duplicates
+                .set_filter(Some(Box::new(move |path| contains_under(&paths, path))));
+            self.filter_label
+                .set_label(&format!("{} — {}", chart.title, bar.label));
+            if let Some(filter_bar) = self.filter_bar.get() {
+                filter_bar.set_visible(true);
+            }
+            if let Some(stack) = self.stack.get() {
+                stack.set_visible_child_name("duplicates");
+            }
+        }
+
+        /// Selects rows of the files, so they are shown even if they are filtered out.
+        fn select_files(&self, files: &[PathBuf]) {
+            let files: HashSet<&Path> = files.iter().map(PathBuf::as_path).collect();
+            self.clear_filter();
+            let mut first = None;
+            self.view.update_selection(|file, _| {
+                let selected = self
+                    .duplicates
+                    .get_fs_path(file)
+                    .is_some_and(|path| files.contains(path.as_path()));
+                if selected && first.is_none() {
+                    first = Some(file.clone());
+                }
+                selected
+            });
+            if let Some(first) = first {
+                self.view.scroll_to(&first);
+            }
+        }
+
+        pub fn clear_filter(&self) {
+            self.duplicates.set_filter(None);
+            if let Some(filter_bar) = self.filter_bar.get() {
+                filter_bar.set_visible(false);
+            }
+        }
+
         async fn show_error(&self, message: impl ToString) {
             user_interaction::notify_error(self.obj().upcast_ref(), &message.to_string()).await;
         }
@@ -251,28 +766,91 @@ impl MainWindow {
         window
     }
 
-    pub fn add_directory(&self, directory: &Path) {
-        self.imp().options.add_directory(directory);
+    pub fn search_options(&self) -> Result<SearchOptions, String> {
+        self.imp().options.get_search_options()
+    }
+
+    /// Fills the form. `profile` is a name of the profile the options come from.
+    pub fn set_search_options(&self, options: &SearchOptions, profile: Option<String>) {
+        let private = self.imp();
+        private.options.set_search_options(options);
+        private.set_profile(profile);
+    }
+
+    pub fn cancel_search(&self) {
+        if let Some(cancellation) = &*self.imp().search.borrow() {
+            cancellation.cancel();
+        }
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.imp().search.borrow().is_some()
+    }
+
+    pub fn status(&self) -> String {
+        if self.is_searching() {
+            "Searching...".into()
+        } else {
+            self.imp()
+                .status
+                .borrow()
+                .clone()
+                .unwrap_or_else(|| "No search has been done.".into())
+        }
+    }
+
+    pub fn add_directories(&self, directories: &[PathBuf]) {
+        let options = &self.imp().options;
+        let known = options.get_directories();
+        for directory in directories {
+            if !known.contains(directory) {
+                options.add_directory(directory);
+            }
+        }
+    }
+
+    /// Paths of files in the results, per group.
+    pub fn result_groups(&self) -> Vec<Vec<PathBuf>> {
+        let private = self.imp();
+        private
+            .duplicates
+            .group_iter()
+            .map(|(_group, files)| {
+                files
+                    .iter()
+                    .filter_map(|file| private.duplicates.get_fs_path(file))
+                    .collect()
+            })
+            .collect()
     }
 
-    fn add_excluded(&self, excluded: Exclusion) {
-        self.imp().options.add_excluded(excluded);
+    /// Shows results of a search which has been done elsewhere.
+    pub fn show_results(&self, options: SearchOptions, results: SearchResults, profile: String) {
+        self.set_search_options(&options, Some(profile));
+        glib::MainContext::default().spawn_local(clone!(@weak self as window => async move {
+            window.imp().on_find_finished(Ok((options, results))).await;
+        }));
+    }
+
+    pub fn start_search(&self) {
+        if let Err(error) = WidgetExt::activate_action(self, "win.find", None) {
+            eprintln!("Cannot start search: {}", error);
+        }
     }
 
     async fn do_save(&self) -> Result<(), Box<dyn Error>> {
         let private = self.imp();
-        let selected = private.view.get_selected_iters();
+        let selected = private.view.get_selected_files();
         let to_save: Vec<PathBuf> = if !selected.is_empty() {
             selected
                 .iter()
-                .filter_map(|iter| private.duplicates.get_fs_path(iter))
+                .filter_map(|file| private.duplicates.get_fs_path(file))
                 .collect()
         } else {
             private
                 .duplicates
-                .group_iter()
-                .flat_map(|(_group, files)| files.into_iter())
-                .filter_map(|iter| private.duplicates.get_fs_path(&iter))
+                .iter()
+                .filter_map(|file| private.duplicates.get_fs_path(&file))
                 .collect()
         };
 
@@ -306,15 +884,25 @@ impl MainWindow {
 
     fn get_selected_fs_path(&self) -> Option<PathBuf> {
         let private = self.imp();
-        let iter = private.view.get_selected_iter()?;
-        private.duplicates.get_fs_path(&iter)
+        let file = private.view.get_selected_file()?;
+        private.duplicates.get_fs_path(&file)
     }
 
-    fn get_path_and_name(&self, iter: &gtk::TreeIter) -> Result<(PathBuf, String), Box<dyn Error>> {
+    /// Path of the selected file or of the archive which contains it.
+    fn get_selected_real_path(&self) -> Option<PathBuf> {
+        let private = self.imp();
+        let file = private.view.get_selected_file()?;
+        private
+            .duplicates
+            .get_archive(&file)
+            .or_else(|| private.duplicates.get_fs_path(&file))
+    }
+
+    fn get_path_and_name(&self, file: &DuplicateFile) -> Result<(PathBuf, String), Box<dyn Error>> {
         let private = self.imp();
         let path = private
             .duplicates
-            .get_fs_path(&iter)
+            .get_fs_path(file)
             .ok_or("Cannot get path of the file")?;
         let name = path
             .file_name()
@@ -327,9 +915,14 @@ impl MainWindow {
 
     async fn do_rename(&self) -> Result<(), Box<dyn Error>> {
         let private = self.imp();
-        let Some(iter) = private.view.get_selected_iter() else { return Ok(()) };
+        let Some(file) = private.view.get_selected_file() else { return Ok(()) };
 
-        let (old_path, old_name) = self.get_path_and_name(&iter)?;
+        let (old_path, old_name) = self.get_path_and_name(&file)?;
+        if private.duplicates.is_read_only(&file) {
+            return Err(
+                format!("{} is inside of an archive and cannot be renamed", old_name).into(),
+            );
+        }
 
         let Some(new_name) = user_interaction::prompt(self.upcast_ref(), "Rename file", "Name:", &old_name)
             .await
@@ -350,72 +943,238 @@ impl MainWindow {
 
         fs::rename(old_path, &new_path)?;
 
-        private.duplicates.set_path(&iter, &new_path);
+        private.duplicates.set_path(&file, &new_path);
 
         Ok(())
     }
 
-    fn delete_file_by_tree_iter(&self, iter: &gtk::TreeIter) -> Result<(), Box<dyn Error>> {
+    fn delete_file(&self, file: &DuplicateFile) -> Result<(), Box<dyn Error>> {
         let private = self.imp();
         let fs_path = private
             .duplicates
-            .get_fs_path(&iter)
-            .ok_or("Cannot get path to file by iter.")?;
+            .get_fs_path(file)
+            .ok_or("Cannot get path to file.")?;
+        if private.duplicates.is_read_only(file) {
+            return Err(format!(
+                "File {} is inside of an archive and cannot be removed.",
+                fs_path.display()
+            )
+            .into());
+        }
         fs::remove_file(&fs_path)
             .map_err(|e| format!("File {} cannot be removed. {}", fs_path.display(), e))?;
         Ok(())
     }
 
-    async fn confirm_deletion(&self, count: usize) -> bool {
-        if self.imp().confirm_delete.get() {
-            let question = if count == 1 {
-                "Are you sure you want to delete this file?".into()
-            } else {
-                format!("Are you sure you want to delete these {} files?", count)
-            };
-            let (confirm, ask_again) =
-                user_interaction::confirm_delete(self.upcast_ref(), &question).await;
-            self.imp().confirm_delete.set(ask_again);
-            confirm
+    /// Removes files of the directory which have copies outside of it, the directory itself and
+    /// its other files are kept. Removed files are forgotten by the results right away, so
+    /// copies in the next deleted directory are not taken for kept ones.
+    fn delete_directory_duplicates(&self, dir: &Path) -> Result<usize, Box<dyn Error>> {
+        let private = self.imp();
+        let files = private.results.borrow().duplicates_under(dir);
+        let mut removed = 0;
+        for path in files {
+            fs::remove_file(&path)
+                .map_err(|e| format!("File {} cannot be removed. {}", path.display(), e))?;
+            private.results.borrow_mut().remove_under(&path);
+            removed += 1;
+        }
+        private.results.borrow_mut().remove_directory(dir);
+        Ok(removed)
+    }
+
+    /// Files with other hard links free no space, so they are always listed for a confirmation.
+    async fn confirm_deletion(&self, files: &[DuplicateFile]) -> bool {
+        const MAX_LISTED: usize = 10;
+        let private = self.imp();
+        let linked: Vec<PathBuf> = files
+            .iter()
+            .filter(|file| !private.duplicates.get_hard_links(file).is_empty())
+            .filter_map(|file| private.duplicates.get_fs_path(file))
+            .collect();
+        if !private.confirm_delete.get() && linked.is_empty() {
+            return true;
+        }
+        let mut question = if files.len() == 1 {
+            "Are you sure you want to delete this file?".into()
         } else {
-            true
+            format!(
+                "Are you sure you want to delete these {} files?",
+                files.len()
+            )
+        };
+        if !linked.is_empty() {
+            question.push_str(
+                "\n\nOther hard links of these files are kept, so no space is freed while \
+                 they remain:",
+            );
+            for path in linked.iter().take(MAX_LISTED) {
+                question.push('\n');
+                question.push_str(&path.to_string_lossy());
+            }
+            if linked.len() > MAX_LISTED {
+                question.push_str(&format!("\nand {} more", linked.len() - MAX_LISTED));
+            }
         }
+        let (confirm, ask_again) =
+            user_interaction::confirm_delete(self.upcast_ref(), &question).await;
+        private.confirm_delete.set(ask_again);
+        confirm
+    }
+
+    /// Directories are always confirmed one by one with the number of files to be deleted.
+    async fn confirm_directory_deletion(&self, dir: &Path) -> bool {
+        let files = self.imp().results.borrow().duplicates_under(dir).len();
+        if files == 0 {
+            self.show_error(format!(
+                "Directory {} has no files with copies outside of it.",
+                dir.display()
+            ))
+            .await;
+            return false;
+        }
+        user_interaction::confirm(
+            self.upcast_ref(),
+            &format!(
+                "Are you sure you want to delete {} files of directory {} which have copies \
+                 outside of it? The directory and its other files are kept.",
+                files,
+                dir.display()
+            ),
+        )
+        .await
     }
 
     async fn show_error(&self, message: impl ToString) {
         user_interaction::notify_error(self.upcast_ref(), &message.to_string()).await;
     }
+
+    /// Searches in a background thread. With `cache` results of the previous search are
+    /// refreshed.
+    fn run_search(&self, options: SearchOptions, cache: Option<ScanCache>) {
+        let private = self.imp();
+        let cancellation = Cancellation::default();
+        *private.search.borrow_mut() = Some(cancellation.clone());
+
+        let progress = user_interaction::ProgressDialog::new(
+            self.upcast_ref(),
+            "Searching...",
+            clone!(@weak self as window => move || window.cancel_search()),
+        );
+        *private.progress.borrow_mut() = Some(progress);
+
+        let sender = private.find_sender.get().unwrap().clone();
+        thread::spawn(move || {
+            let duplicates = match cache {
+                Some(cache) => refresh_duplicate_groups(&options, cache, &cancellation),
+                None => find_duplicate_groups(&options, &cancellation),
+            };
+            let _ = sender.send(
+                duplicates
+                    .map(|duplicates| (options, duplicates))
+                    .map_err(|err| err.to_string()),
+            );
+        });
+    }
+
+    async fn do_schedule_profile(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        let current = scheduler::schedule_of(name);
+        let intervals: Vec<&str> = Interval::ALL.iter().map(|i| i.name()).collect();
+        let Some(answer) = user_interaction::prompt_schedule(
+            self.upcast_ref(),
+            &format!("Schedule \"{}\"", name),
+            &intervals,
+            current
+                .as_ref()
+                .and_then(|s| Interval::ALL.iter().position(|i| *i == s.interval)),
+            &current
+                .as_ref()
+                .map(|s| s.threshold.to_string())
+                .unwrap_or_else(|| "100MB".into()),
+        )
+        .await
+        else {
+            return Ok(());
+        };
+        let schedule = match answer.interval {
+            Some(index) => Some(Schedule {
+                profile: name.to_owned(),
+                interval: Interval::ALL[index],
+                threshold: parse_bytes(&answer.threshold)?,
+            }),
+            None => None,
+        };
+        scheduler::set_schedule(name, schedule)
+    }
+
+    async fn do_save_profile(&self) -> Result<(), Box<dyn Error>> {
+        let private = self.imp();
+        let options = private.options.get_search_options()?;
+        let current = private.profile.borrow().clone().unwrap_or_default();
+        let Some(name) = user_interaction::prompt(self.upcast_ref(), "Save profile", "Name:", &current)
+            .await
+            .map(|name| name.trim().to_owned())
+            else { return Ok(()) };
+        profiles::validate_name(&name)?;
+
+        if name != current
+            && profiles::list().contains(&name)
+            && !user_interaction::confirm(
+                self.upcast_ref(),
+                &format!("Do you want to overwrite profile \"{}\"?", name),
+            )
+            .await
+        {
+            return Ok(());
+        }
+
+        profiles::save(&name, &options)?;
+        private.refresh_profiles();
+        private.set_profile(Some(name));
+        Ok(())
+    }
 }
 
 #[awesome_glib::actions]
 impl MainWindow {
     async fn find(&self) {
         let private = self.imp();
+        if self.is_searching() {
+            return;
+        }
 
-        let search_dirs = private.options.get_directories();
-        if search_dirs.is_empty() {
+        let options = match private.options.get_search_options() {
+            Ok(options) => options,
+            Err(error) => {
+                self.show_error(error).await;
+                return;
+            }
+        };
+        if options.paths.is_empty() {
             self.show_error("No search paths specified").await;
             return;
         }
-        let excluded = private.options.get_excluded();
-
-        let min_size: u64 = private.options.get_min_size();
-        let recurse = private.options.get_recurse();
 
         private.duplicates.clear();
+        private.last_search.replace(None);
+        self.run_search(options, None);
+    }
 
-        let progress = user_interaction::ProgressDialog::new(self.upcast_ref(), "Searching...");
-        *private.progress.borrow_mut() = Some(progress);
-
-        let sender = private.find_sender.get().unwrap().clone();
-        thread::spawn(move || {
-            let duplicates = find_duplicate_groups(&search_dirs, &excluded, min_size, recurse);
-            let _ = sender.send(duplicates.map_err(|err| err.to_string()));
-        });
+    /// Repeats the last search, reading only what has changed since.
+    async fn refresh(&self) {
+        let private = self.imp();
+        if self.is_searching() {
+            return;
+        }
+        let Some((options, cache)) = private.last_search.borrow().clone() else {
+            self.show_error("Nothing to refresh, search first").await;
+            return;
+        };
+        self.run_search(options, Some(cache));
     }
 
     async fn open(&self) {
-        if let Some(path) = self.get_selected_fs_path() {
+        if let Some(path) = self.get_selected_real_path() {
             if let Err(error) = xdg_open(&path) {
                 self.show_error(error).await;
             }
@@ -424,7 +1183,7 @@ impl MainWindow {
 
     async fn open_directory(&self) {
         if let Some(dir) = self
-            .get_selected_fs_path()
+            .get_selected_real_path()
             .and_then(|path| path.parent().map(|p| p.to_path_buf()))
         {
             if let Err(error) = xdg_open(&dir) {
@@ -450,18 +1209,14 @@ impl MainWindow {
         let private = self.imp();
         if let Some(path) = self.get_selected_fs_path() {
             if let Some(dir) = path.parent() {
-                for (_group, files) in private.duplicates.group_iter() {
-                    for file in files {
-                        if private
+                private.view.update_selection(|file, selected| {
+                    selected
+                        || private
                             .duplicates
-                            .get_fs_path(&file)
+                            .get_fs_path(file)
                             .and_then(|p| p.parent().map(|p| p.to_path_buf()))
                             == Some(dir.to_path_buf())
-                        {
-                            private.view.get_selection().select_iter(&file);
-                        }
-                    }
-                }
+                });
             }
         }
     }
@@ -490,99 +1245,116 @@ impl MainWindow {
             }
         };
 
-        let selection = private.view.get_selection();
-        for (_group, files) in private.duplicates.group_iter() {
-            for file_iter in files {
-                let fs_path = private.duplicates.get_fs_path(&file_iter).unwrap();
-                if pattern.matches_path(&fs_path) {
-                    if select {
-                        selection.select_iter(&file_iter);
-                    } else {
-                        selection.unselect_iter(&file_iter);
-                    }
-                }
+        private.view.update_selection(|file, selected| {
+            let fs_path = private.duplicates.get_fs_path(file).unwrap();
+            if pattern.matches_path(&fs_path) {
+                select
+            } else {
+                selected
             }
-        }
+        });
     }
 
     fn select_all_but(&self, which: String) {
         fn find_row_to_unselect<'i>(
             model: &duplicates_list::DuplicatesStore,
-            files: &'i [gtk::TreeIter],
+            files: &'i [DuplicateFile],
             which: &str,
-        ) -> Option<&'i gtk::TreeIter> {
+        ) -> Option<&'i DuplicateFile> {
             match which {
                 "first" => files.first(),
-                "newest" => files.iter().max_by_key(|iter| model.modified(iter)),
-                "oldest" => files.iter().min_by_key(|iter| model.modified(iter)),
+                "newest" => files.iter().max_by_key(|file| model.modified(file)),
+                "oldest" => files.iter().min_by_key(|file| model.modified(file)),
                 _ => None,
             }
         }
 
         let private = self.imp();
-        let selection = private.view.get_selection();
-        for (_group, files) in private.duplicates.group_iter() {
-            for file in &files {
-                selection.select_iter(file);
-            }
-            if let Some(unselect) = find_row_to_unselect(&private.duplicates, &files, &which) {
-                selection.unselect_iter(unselect);
-            }
-        }
+        let unselected: HashSet<DuplicateFile> = private
+            .duplicates
+            .group_iter()
+            .filter_map(|(_group, files)| {
+                find_row_to_unselect(&private.duplicates, &files, &which).cloned()
+            })
+            .collect();
+        private
+            .view
+            .update_selection(|file, _| !unselected.contains(file));
     }
 
     fn select_toggle(&self) {
-        let private = self.imp();
-
-        let selection = private.view.get_selection();
-        for iter in private.duplicates.iter() {
-            if !private.duplicates.is_group(&iter) {
-                if selection.iter_is_selected(&iter) {
-                    selection.unselect_iter(&iter);
-                } else {
-                    selection.select_iter(&iter);
-                }
-            }
-        }
+        self.imp()
+            .view
+            .update_selection(|_file, selected| !selected);
     }
 
     fn unselect_all(&self) {
-        self.imp().view.get_selection().unselect_all();
+        self.imp().view.unselect_all();
+    }
+
+    fn clear_filter(&self) {
+        self.imp().clear_filter();
     }
 
     async fn delete(&self) {
-        let selected = self.imp().view.get_selected_iters();
+        let private = self.imp();
+        let selected = private.view.get_selected_files();
 
-        let count = selected.len();
-        if count == 0 {
+        if selected.is_empty() {
             self.show_error("No file is selected").await;
             return;
         }
-        if !self.confirm_deletion(count).await {
-            return;
+        let (directories, mut files): (Vec<DuplicateFile>, Vec<DuplicateFile>) = selected
+            .into_iter()
+            .partition(|file| private.duplicates.get_directory_files(file).is_some());
+        if !files.is_empty() && !self.confirm_deletion(&files).await {
+            files.clear();
         }
 
-        let mut deleted: Vec<gtk::TreeIter> = Vec::new();
+        let mut deleted: Vec<DuplicateFile> = Vec::new();
         let mut errors = Vec::new();
-        for iter in selected {
-            match self.delete_file_by_tree_iter(&iter) {
+        let mut removed = 0;
+        for file in files {
+            match self.delete_file(&file) {
                 Ok(_) => {
-                    deleted.push(iter);
+                    removed += 1;
+                    if let Some(path) = private.duplicates.get_fs_path(&file) {
+                        private.results.borrow_mut().remove_under(&path);
+                    }
+                    deleted.push(file);
                 }
                 Err(error) => {
                     errors.push(error);
                 }
             }
         }
+        for dir in directories {
+            let Some(path) = private.duplicates.get_fs_path(&dir) else {
+                continue;
+            };
+            if !self.confirm_directory_deletion(&path).await {
+                continue;
+            }
+            match self.delete_directory_duplicates(&path) {
+                Ok(files) => {
+                    removed += files;
+                    deleted.push(dir);
+                }
+                Err(error) => {
+                    errors.push(error);
+                }
+            }
+        }
+        if deleted.is_empty() && errors.is_empty() {
+            return;
+        }
 
-        self.imp().duplicates.remove_all(&deleted);
+        private.duplicates.remove_all(&deleted);
+        private.show_statistics();
 
         if errors.is_empty() {
-            user_interaction::notify_info(
-                self.upcast_ref(),
-                &format!("{} items deleted", deleted.len()),
-            )
-            .await;
+            user_interaction::notify_info(self.upcast_ref(), &format!("{} items deleted", removed))
+                .await;
         } else {
             let mut error_message = String::from("Following errors happened:\n");
             for error in errors {
@@ -591,7 +1363,7 @@ impl MainWindow {
             }
             user_interaction::notify_detailed(
                 self.upcast_ref(),
-                &format!("{} items deleted", deleted.len()),
+                &format!("{} items deleted", removed),
                 &error_message,
             )
             .await;
@@ -603,6 +1375,60 @@ impl MainWindow {
             self.show_error(error).await;
         }
     }
+
+    async fn load_profile(&self, name: String) {
+        let private = self.imp();
+        match profiles::load(&name) {
+            Ok(options) => {
+                private.options.set_search_options(&options);
+                private.set_profile(Some(name));
+            }
+            Err(error) => self.show_error(error).await,
+        }
+    }
+
+    async fn save_profile(&self) {
+        if let Err(error) = self.do_save_profile().await {
+            self.show_error(error).await;
+        }
+    }
+
+    async fn delete_profile(&self, name: String) {
+        let private = self.imp();
+        if !user_interaction::confirm(
+            self.upcast_ref(),
+            &format!("Do you want to delete profile \"{}\"?", name),
+        )
+        .await
+        {
+            return;
+        }
+        if let Err(error) = profiles::delete(&name) {
+            self.show_error(error).await;
+        }
+        if let Err(error) = scheduler::set_schedule(&name, None) {
+            self.show_error(error).await;
+        }
+        private.refresh_profiles();
+        if private.profile.borrow().as_deref() == Some(name.as_str()) {
+            private.set_profile(None);
+        }
+    }
+
+    async fn schedule_profile(&self, name: String) {
+        if let Err(error) = self.do_schedule_profile(&name).await {
+            self.show_error(error).await;
+        }
+    }
+
+    fn reset_options(&self) {
+        let private = self.imp();
+        private
+            .options
+            .set_search_options(&default_search_options());
+        private.confirm_delete.set(true);
+        private.set_profile(None);
+    }
 }
 
 fn save_file(destination_path: &Path, paths: &[PathBuf]) -> io::Result<()> {
@@ -0,0 +1,76 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+
+const MOUNTINFO: &str = "/proc/self/mountinfo";
+
+/// Same encoding of a device number as glibc's `makedev`.
+fn makedev(major: u64, minor: u64) -> u64 {
+    ((major & 0xffff_f000) << 32)
+        | ((major & 0x0000_0fff) << 8)
+        | ((minor & 0xffff_ff00) << 12)
+        | (minor & 0x0000_00ff)
+}
+
+/// Parses device and file system type of a line of `mountinfo`. See proc(5) for the format.
+fn parse_mount(line: &str) -> Option<(u64, &str)> {
+    let (mount, fs) = line.split_once(" - ")?;
+    let (major, minor) = mount.split(' ').nth(2)?.split_once(':')?;
+    let fs_type = fs.split(' ').next()?;
+    Some((makedev(major.parse().ok()?, minor.parse().ok()?), fs_type))
+}
+
+/// Devices of mounted file systems of given types.
+pub fn devices_of_types(fs_types: &[String]) -> io::Result<HashSet<u64>> {
+    if fs_types.is_empty() {
+        return Ok(HashSet::new());
+    }
+    let mountinfo = fs::read_to_string(MOUNTINFO)?;
+    let devices = mountinfo
+        .lines()
+        .filter_map(parse_mount)
+        .filter(|(_, fs_type)| fs_types.iter().any(|t| t == fs_type))
+        .map(|(device, _)| device)
+        .collect();
+    Ok(devices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_numbers_are_encoded_as_in_glibc() {
+        assert_eq!(makedev(8, 1), 0x801);
+        assert_eq!(makedev(259, 3), 0x10303);
+        assert_eq!(makedev(0, 300), 0x10002c);
+        assert_eq!(makedev(0x1234, 0x567), 0x1000_0052_3467);
+    }
+
+    #[test]
+    fn mount_lines() {
+        assert_eq!(
+            parse_mount("36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw"),
+            Some((makedev(98, 0), "ext3"))
+        );
+        assert_eq!(
+            parse_mount("25 1 8:1 / / rw - ext4 /dev/sda1 rw"),
+            Some((makedev(8, 1), "ext4"))
+        );
+        // spaces in paths are escaped, so they do not shift fields
+        assert_eq!(
+            parse_mount(
+                r"100 29 0:45 / /media/My\040Disk rw,relatime shared:1 - vfat /dev/sdb1 rw"
+            ),
+            Some((makedev(0, 45), "vfat"))
+        );
+    }
+
+    #[test]
+    fn malformed_mount_lines() {
+        assert_eq!(parse_mount(""), None);
+        assert_eq!(parse_mount("25 1 8:1 / / rw ext4 /dev/sda1 rw"), None);
+        assert_eq!(parse_mount("25 1 8-1 / / rw - ext4 /dev/sda1 rw"), None);
+        assert_eq!(parse_mount("25 1 x:1 / / rw - ext4 /dev/sda1 rw"), None);
+    }
+}
@@ -0,0 +1,59 @@
+use super::{has_extension, Normalizer};
+use crate::find_duplicates::FileInfo;
+
+const SOI: u8 = 0xD8;
+const SOS: u8 = 0xDA;
+const APP1: u8 = 0xE1;
+
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const XMP_EXTENSION_HEADER: &[u8] = b"http://ns.adobe.com/xmp/extension/\0";
+
+/// Strips EXIF and XMP segments from JPEG files.
+pub struct JpegMetadata;
+
+fn is_metadata(marker: u8, payload: &[u8]) -> bool {
+    marker == APP1
+        && [EXIF_HEADER, XMP_HEADER, XMP_EXTENSION_HEADER]
+            .iter()
+            .any(|header| payload.starts_with(header))
+}
+
+impl Normalizer for JpegMetadata {
+    fn accepts(&self, fi: &FileInfo) -> bool {
+        has_extension(fi, &["jpg", "jpeg", "jpe", "jfif"])
+    }
+
+    fn content<'d>(&self, data: &'d [u8]) -> Option<Vec<&'d [u8]>> {
+        if data.get(..2)? != [0xFF, SOI] {
+            return None;
+        }
+
+        let mut parts = vec![&data[..2]];
+        let mut pos = 2;
+        loop {
+            if *data.get(pos)? != 0xFF {
+                return None;
+            }
+            // markers may be preceded by fill bytes
+            while *data.get(pos + 1)? == 0xFF {
+                pos += 1;
+            }
+            let marker = data[pos + 1];
+            if marker == SOS {
+                // entropy-coded image data till the end
+                parts.push(&data[pos..]);
+                return Some(parts);
+            }
+            let length = u16::from_be_bytes([*data.get(pos + 2)?, *data.get(pos + 3)?]) as usize;
+            if length < 2 {
+                return None;
+            }
+            let segment = data.get(pos..pos + 2 + length)?;
+            if !is_metadata(marker, &segment[4..]) {
+                parts.push(segment);
+            }
+            pos += 2 + length;
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use crate::find_duplicates::{Cancellation, ContentCache, FileHash, FileInfo};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+mod jpeg;
+mod mp3;
+
+/// Normalizer removes metadata from a file, so files which differ only in metadata get the same
+/// content hash.
+pub trait Normalizer: Send + Sync {
+    /// Checks if the normalizer knows the file by its name.
+    fn accepts(&self, fi: &FileInfo) -> bool;
+
+    /// Returns parts of the file which are left after stripping metadata or `None` if data is
+    /// not in the expected format.
+    fn content<'d>(&self, data: &'d [u8]) -> Option<Vec<&'d [u8]>>;
+}
+
+pub fn normalizers() -> Vec<Box<dyn Normalizer>> {
+    vec![Box::new(jpeg::JpegMetadata), Box::new(mp3::Mp3Tags)]
+}
+
+fn has_extension(fi: &FileInfo, extensions: &[&str]) -> bool {
+    fi.path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            extensions
+                .iter()
+                .any(|known| ext.eq_ignore_ascii_case(known))
+        })
+}
+
+/// Hash of a file without metadata. Returns `None` if the file has no suitable normalizer.
+fn content_hash(
+    normalizers: &[Box<dyn Normalizer>],
+    fi: &FileInfo,
+) -> io::Result<Option<FileHash>> {
+    let Some(normalizer) = normalizers.iter().find(|n| n.accepts(fi)) else {
+        return Ok(None);
+    };
+    let data = fs::read(&fi.path)?;
+    let Some(parts) = normalizer.content(&data) else {
+        return Ok(None);
+    };
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    Ok(Some(hasher.finalize()))
+}
+
+/// Groups files which have the same content after stripping metadata. Hashes are taken from
+/// `hashes` if the file did not change.
+pub fn group_by_content(
+    normalizers: &[Box<dyn Normalizer>],
+    fis: Vec<FileInfo>,
+    hashes: &mut ContentCache<Option<FileHash>>,
+    cancellation: &Cancellation,
+) -> io::Result<Vec<Vec<FileInfo>>> {
+    let mut groups: HashMap<_, Vec<FileInfo>> = HashMap::new();
+    for fi in fis {
+        cancellation.check()?;
+        if let Some(hash) = hashes.get_or_compute(&fi, |fi| content_hash(normalizers, fi))? {
+            groups.entry(hash).or_default().push(fi);
+        }
+    }
+    groups.retain(|_, fis| fis.len() >= 2);
+    Ok(groups.into_values().collect())
+}
@@ -0,0 +1,64 @@
+use super::{has_extension, Normalizer};
+use crate::find_duplicates::FileInfo;
+
+const ID3V2_HEADER_SIZE: usize = 10;
+const ID3V1_SIZE: usize = 128;
+const ID3V1_ENHANCED_SIZE: usize = 227;
+
+/// Strips ID3v1 and ID3v2 tags from MP3 files.
+pub struct Mp3Tags;
+
+fn synchsafe(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .fold(0, |size, byte| (size << 7) | (*byte & 0x7F) as usize)
+}
+
+/// Size of ID3v2 tag (including header and footer) in the beginning of data.
+fn id3v2_size(data: &[u8]) -> Option<usize> {
+    let header = data.get(..ID3V2_HEADER_SIZE)?;
+    if &header[..3] != b"ID3" {
+        return None;
+    }
+    let has_footer = header[5] & 0x10 != 0;
+    let size = ID3V2_HEADER_SIZE
+        + synchsafe(&header[6..10])
+        + if has_footer { ID3V2_HEADER_SIZE } else { 0 };
+    Some(size)
+}
+
+/// Size of ID3v2 tag with footer in the end of data.
+fn id3v2_appended_size(data: &[u8]) -> Option<usize> {
+    let footer = data.get(data.len().checked_sub(ID3V2_HEADER_SIZE)?..)?;
+    if &footer[..3] != b"3DI" {
+        return None;
+    }
+    Some(2 * ID3V2_HEADER_SIZE + synchsafe(&footer[6..10]))
+}
+
+impl Normalizer for Mp3Tags {
+    fn accepts(&self, fi: &FileInfo) -> bool {
+        has_extension(fi, &["mp3"])
+    }
+
+    fn content<'d>(&self, mut data: &'d [u8]) -> Option<Vec<&'d [u8]>> {
+        while let Some(size) = id3v2_size(data) {
+            data = data.get(size..)?;
+        }
+
+        if data.len() >= ID3V1_SIZE && data[data.len() - ID3V1_SIZE..].starts_with(b"TAG") {
+            data = &data[..data.len() - ID3V1_SIZE];
+            if data.len() >= ID3V1_ENHANCED_SIZE
+                && data[data.len() - ID3V1_ENHANCED_SIZE..].starts_with(b"TAG+")
+            {
+                data = &data[..data.len() - ID3V1_ENHANCED_SIZE];
+            }
+        }
+
+        if let Some(size) = id3v2_appended_size(data) {
+            data = data.get(..data.len().checked_sub(size)?)?;
+        }
+
+        Some(vec![data])
+    }
+}
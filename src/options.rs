@@ -1,8 +1,13 @@
-use crate::exclusion::Exclusion;
+use crate::audio::AudioMatch;
+use crate::exclusion::{validate_regex, Exclusion, PathPattern};
+use crate::filter::parse_filter;
+use crate::find_duplicates::{SearchOptions, SymlinkPolicy};
 use crate::gtk_prelude::*;
 use crate::path_choose::select_dir;
+use crate::similar_images::ImageHash;
+use crate::size_range::{parse_size_range, SizeRange};
 use crate::string_list::StringList;
-use crate::user_interaction::prompt;
+use crate::user_interaction::prompt_pattern;
 use crate::utils::{horizontal_expander, scrolled};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
@@ -34,8 +39,34 @@ pub struct Options {
     container: gtk::Grid,
     directories: StringList<Directory>,
     excluded: StringList<Exclusion>,
-    recurse: gtk::CheckButton,
-    min_size: gtk::Entry,
+    max_depth: gtk::SpinButton,
+    size: gtk::Entry,
+    filter: gtk::Entry,
+    ignore_files: gtk::CheckButton,
+    find_directories: gtk::CheckButton,
+    directory_similarity: gtk::SpinButton,
+    similar_images: gtk::CheckButton,
+    image_hash: gtk::DropDown,
+    image_distance: gtk::SpinButton,
+    audio_match: gtk::DropDown,
+    ignore_metadata: gtk::CheckButton,
+    search_archives: gtk::CheckButton,
+    symlinks: gtk::DropDown,
+    one_file_system: gtk::CheckButton,
+    skip_file_systems: gtk::Entry,
+}
+
+/// Highlights the entry with invalid input and shows the error in its tooltip.
+fn show_entry_error(entry: &gtk::Entry, error: Option<&str>) {
+    if let Some(error) = error {
+        entry.add_css_class("error");
+        entry.set_secondary_icon_name(Some("dialog-error-symbolic"));
+        entry.set_secondary_icon_tooltip_text(Some(error));
+    } else {
+        entry.remove_css_class("error");
+        entry.set_secondary_icon_name(None);
+        entry.set_secondary_icon_tooltip_text(None);
+    }
 }
 
 fn get_window(widget: &impl IsA<gtk::Widget>) -> Option<gtk::Window> {
@@ -48,13 +79,23 @@ async fn pick_directory(window: &gtk::Window) -> Option<PathBuf> {
     Some(path)
 }
 
-async fn pick_pattern(window: &gtk::Window) -> Option<String> {
-    let pattern = prompt(&window, "Add pattern", "pattern", "").await?;
-    if pattern.is_empty() {
-        None
-    } else {
-        Some(pattern)
-    }
+async fn pick_pattern(
+    window: &gtk::Window,
+    title: &str,
+    validate: impl Fn(&str) -> Result<(), String> + 'static,
+) -> Option<PathPattern> {
+    let answer = prompt_pattern(window, title, validate).await?;
+    Some(PathPattern {
+        pattern: answer.pattern,
+        case_sensitive: answer.case_sensitive,
+        basename: answer.basename,
+    })
+}
+
+fn validate_glob(pattern: &str) -> Result<(), String> {
+    glob::Pattern::new(pattern)
+        .map(|_| ())
+        .map_err(|error| error.to_string())
 }
 
 fn add_directory_button(string_list: &StringList<Directory>) -> gtk::Button {
@@ -91,16 +132,18 @@ fn add_excluded_directory_button(string_list: &StringList<Exclusion>) -> gtk::Bu
     button
 }
 
-fn add_exclusion_pattern_button(string_list: &StringList<Exclusion>) -> gtk::Button {
-    let button = gtk::Button::builder()
-        .label("Add pattern")
-        .hexpand(false)
-        .build();
+fn add_pattern_button(
+    string_list: &StringList<Exclusion>,
+    label: &'static str,
+    validate: fn(&str) -> Result<(), String>,
+    exclusion: fn(PathPattern) -> Exclusion,
+) -> gtk::Button {
+    let button = gtk::Button::builder().label(label).hexpand(false).build();
     button.connect_clicked(clone!(@weak string_list => move |button| {
         if let Some(window) = get_window(button) {
             glib::MainContext::default().spawn_local(async move {
-                if let Some(new_value) = pick_pattern(&window).await {
-                    string_list.append(Exclusion::Pattern(new_value));
+                if let Some(new_value) = pick_pattern(&window, label, validate).await {
+                    string_list.append(exclusion(new_value));
                 }
             });
         }
@@ -155,9 +198,11 @@ impl Options {
             .column_spacing(8)
             .row_spacing(8)
             .build();
+        let mut row = 0;
 
         let directories_label = form_label("Directories to search");
-        container.attach(&directories_label, 0, 0, 3, 1);
+        container.attach(&directories_label, 0, row, 3, 1);
+        row += 1;
 
         let directories_container = gtk::Box::builder()
             .homogeneous(false)
@@ -175,50 +220,237 @@ impl Options {
         ]);
         directories_container.append(&directories_buttons);
 
-        container.attach(&directories_container, 0, 1, 3, 1);
+        container.attach(&directories_container, 0, row, 3, 1);
+        row += 1;
 
         let excluded_label = form_label("Paths to exclude");
-        container.attach(&excluded_label, 0, 2, 3, 1);
+        container.attach(&excluded_label, 0, row, 3, 1);
+        row += 1;
 
         let excluded_view = StringList::new();
         let excluded = scrolled(&excluded_view.get_widget(), true);
-        container.attach(&excluded, 0, 3, 2, 1);
+        container.attach(&excluded, 0, row, 2, 1);
 
         let excluded_buttons = button_column(&[
             add_excluded_directory_button(&excluded_view),
-            add_exclusion_pattern_button(&excluded_view),
+            add_pattern_button(
+                &excluded_view,
+                "Add pattern",
+                validate_glob,
+                Exclusion::Pattern,
+            ),
+            add_pattern_button(
+                &excluded_view,
+                "Add regex",
+                validate_regex,
+                Exclusion::Regex,
+            ),
+            add_pattern_button(
+                &excluded_view,
+                "Include regex",
+                validate_regex,
+                Exclusion::IncludeRegex,
+            ),
             remove_selection_button(&excluded_view),
             clear_button(&excluded_view),
         ]);
-        container.attach(&excluded_buttons, 2, 3, 1, 1);
+        container.attach(&excluded_buttons, 2, row, 1, 1);
+        row += 1;
+
+        let max_depth_label = form_label("Maximum depth:");
+        container.attach(&max_depth_label, 0, row, 1, 1);
+
+        let max_depth = gtk::SpinButton::with_range(0.0, 1000.0, 1.0);
+        max_depth.set_tooltip_text(Some(
+            "1 means files of the searched directories only, 0 means no limit",
+        ));
+        max_depth.set_value(0.0);
+        container.attach(&max_depth, 1, row, 2, 1);
+        row += 1;
+
+        let size_label = form_label("File size:");
+        container.attach(&size_label, 0, row, 1, 1);
+
+        let size = gtk::Entry::builder()
+            .tooltip_text(
+                "Minimal size or a condition, e.g. \"+10k -100M\" or \"1MB..1.5GiB\". \
+                 Suffixes of find -size are supported.",
+            )
+            .text("1")
+            .hexpand(true)
+            .vexpand(false)
+            .build();
+        size.connect_changed(|entry| {
+            let error = parse_size_range(&entry.text()).err();
+            show_entry_error(entry, error.as_deref());
+        });
+        container.attach(&size, 1, row, 2, 1);
+        row += 1;
+
+        let filter_label = form_label("Only files matching:");
+        container.attach(&filter_label, 0, row, 1, 1);
+
+        let filter = gtk::Entry::builder()
+            .tooltip_text(
+                "Predicates older:AGE, newer:AGE (like 1y, 6mo, 2w or 2020-01-31), \
+                 ext:jpg,png (or @image, @audio, @video, @media), uid:N, gid:N, \
+                 perm:MODE (as find -perm) and type:f|l|a combined with and, or, not",
+            )
+            .placeholder_text("ext:@media and older:1y and not uid:0")
+            .hexpand(true)
+            .vexpand(false)
+            .build();
+        filter.connect_changed(|entry| {
+            let error = parse_filter(&entry.text()).err();
+            show_entry_error(entry, error.as_deref());
+        });
+        container.attach(&filter, 1, row, 2, 1);
+        row += 1;
+
+        let ignore_files = gtk::CheckButton::builder()
+            .label("skip files listed in .gitignore, .ignore and .dupignore?")
+            .active(false)
+            .build();
+        container.attach(&ignore_files, 0, row, 3, 1);
+        row += 1;
 
-        let recurse = gtk::CheckButton::builder()
-            .label("recurse?")
-            .active(true)
+        let find_directories = gtk::CheckButton::builder()
+            .label("find duplicate directories?")
+            .active(false)
+            .build();
+        container.attach(&find_directories, 0, row, 3, 1);
+        row += 1;
+
+        let directory_similarity_label = form_label("Directory similarity, %:");
+        container.attach(&directory_similarity_label, 0, row, 1, 1);
+
+        let directory_similarity = gtk::SpinButton::with_range(50.0, 100.0, 1.0);
+        directory_similarity.set_tooltip_text(Some(
+            "Directories with lower similarity are not reported. 100% means identical content.",
+        ));
+        directory_similarity.set_value(90.0);
+        find_directories
+            .bind_property("active", &directory_similarity, "sensitive")
+            .flags(glib::BindingFlags::SYNC_CREATE)
             .build();
+        container.attach(&directory_similarity, 1, row, 2, 1);
+        row += 1;
 
-        container.attach(&recurse, 0, 4, 3, 1);
+        let similar_images = gtk::CheckButton::builder()
+            .label("find similar images?")
+            .active(false)
+            .build();
+        container.attach(&similar_images, 0, row, 3, 1);
+        row += 1;
+
+        let image_hash_label = form_label("Image hash:");
+        container.attach(&image_hash_label, 0, row, 1, 1);
+
+        let image_hash_names: Vec<&str> = ImageHash::ALL.iter().map(|hash| hash.name()).collect();
+        let image_hash = gtk::DropDown::from_strings(&image_hash_names);
+        image_hash.set_selected(1);
+        container.attach(&image_hash, 1, row, 2, 1);
+        row += 1;
+
+        let image_distance_label = form_label("Maximal distance:");
+        container.attach(&image_distance_label, 0, row, 1, 1);
+
+        let image_distance = gtk::SpinButton::with_range(0.0, 32.0, 1.0);
+        image_distance.set_tooltip_text(Some(
+            "Number of differing bits in image hashes. Lower values find closer images.",
+        ));
+        image_distance.set_value(8.0);
+        container.attach(&image_distance, 1, row, 2, 1);
+        row += 1;
+
+        let audio_match_label = form_label("Compare audio files by:");
+        container.attach(&audio_match_label, 0, row, 1, 1);
+
+        let audio_match_names: Vec<&str> = AudioMatch::ALL.iter().map(|m| m.name()).collect();
+        let audio_match = gtk::DropDown::from_strings(&audio_match_names);
+        audio_match.set_selected(0);
+        container.attach(&audio_match, 1, row, 2, 1);
+        row += 1;
+
+        let ignore_metadata = gtk::CheckButton::builder()
+            .label("ignore metadata (EXIF, XMP, ID3)?")
+            .tooltip_text("Files which differ in metadata only are reported as content match")
+            .active(false)
+            .build();
+        container.attach(&ignore_metadata, 0, row, 3, 1);
+        row += 1;
 
-        let min_size_label = form_label("Minimum file size:");
-        container.attach(&min_size_label, 0, 5, 1, 1);
+        let search_archives = gtk::CheckButton::builder()
+            .label("look inside archives (ZIP, TAR)?")
+            .active(false)
+            .build();
+        container.attach(&search_archives, 0, row, 3, 1);
+        row += 1;
+
+        let symlinks_label = form_label("Symbolic links:");
+        container.attach(&symlinks_label, 0, row, 1, 1);
+
+        let symlinks_names: Vec<&str> = SymlinkPolicy::ALL.iter().map(|p| p.name()).collect();
+        let symlinks = gtk::DropDown::from_strings(&symlinks_names);
+        symlinks.set_tooltip_text(Some(
+            "Reported links are shown next to their targets but do not count as duplicates",
+        ));
+        symlinks.set_selected(0);
+        container.attach(&symlinks, 1, row, 2, 1);
+        row += 1;
+
+        let one_file_system = gtk::CheckButton::builder()
+            .label("stay on one file system?")
+            .tooltip_text("Do not descend into directories on other file systems (like find -xdev)")
+            .active(false)
+            .build();
+        container.attach(&one_file_system, 0, row, 3, 1);
+        row += 1;
 
-        let min_size = gtk::Entry::builder()
-            .tooltip_text("Using find -size syntax")
-            .text("1")
+        let skip_file_systems_label = form_label("Skip file system types:");
+        container.attach(&skip_file_systems_label, 0, row, 1, 1);
+
+        let skip_file_systems = gtk::Entry::builder()
+            .tooltip_text("Comma separated types as in /proc/self/mountinfo")
+            .placeholder_text("nfs, cifs, fuse.sshfs")
             .hexpand(true)
             .vexpand(false)
             .build();
-        container.attach(&min_size, 1, 5, 2, 1);
+        container.attach(&skip_file_systems, 1, row, 2, 1);
+        row += 1;
+
+        for widget in [
+            image_hash.upcast_ref::<gtk::Widget>(),
+            image_distance.upcast_ref(),
+        ] {
+            similar_images
+                .bind_property("active", widget, "sensitive")
+                .flags(glib::BindingFlags::SYNC_CREATE)
+                .build();
+        }
 
-        // artificial expander for the column #1
-        container.attach(&horizontal_expander(), 1, 100, 1, 1);
+        // artificial expander for the column #1, below all rows
+        container.attach(&horizontal_expander(), 1, row, 1, 1);
 
         Self {
             container,
             directories: directories_view,
             excluded: excluded_view,
-            recurse,
-            min_size,
+            max_depth,
+            size,
+            filter,
+            ignore_files,
+            find_directories,
+            directory_similarity,
+            similar_images,
+            image_hash,
+            image_distance,
+            audio_match,
+            ignore_metadata,
+            search_archives,
+            symlinks,
+            one_file_system,
+            skip_file_systems,
         }
     }
 
@@ -242,11 +474,101 @@ impl Options {
         self.excluded.to_vec()
     }
 
-    pub fn get_recurse(&self) -> bool {
-        self.recurse.is_active()
+    pub fn get_max_depth(&self) -> Option<u32> {
+        match self.max_depth.value_as_int() {
+            0 => None,
+            depth => Some(depth as u32),
+        }
+    }
+
+    pub fn get_size_range(&self) -> Result<SizeRange, String> {
+        parse_size_range(&self.size.text())
+    }
+
+    pub fn get_search_options(&self) -> Result<SearchOptions, String> {
+        let size = self.get_size_range()?;
+        Ok(SearchOptions {
+            paths: self.get_directories(),
+            exclude: self.get_excluded(),
+            min_size: size.min,
+            max_size: size.max,
+            max_depth: self.get_max_depth(),
+            filter: parse_filter(&self.filter.text())?,
+            ignore_files: self.ignore_files.is_active(),
+            find_directories: self.find_directories.is_active(),
+            directory_similarity: self.directory_similarity.value_as_int() as u8,
+            similar_images: self.similar_images.is_active(),
+            image_hash: ImageHash::ALL
+                .get(self.image_hash.selected() as usize)
+                .copied()
+                .unwrap_or(ImageHash::Difference),
+            image_distance: self.image_distance.value_as_int() as u32,
+            audio_match: AudioMatch::ALL
+                .get(self.audio_match.selected() as usize)
+                .copied()
+                .unwrap_or(AudioMatch::Exact),
+            ignore_metadata: self.ignore_metadata.is_active(),
+            search_archives: self.search_archives.is_active(),
+            symlinks: SymlinkPolicy::ALL
+                .get(self.symlinks.selected() as usize)
+                .copied()
+                .unwrap_or(SymlinkPolicy::Ignore),
+            one_file_system: self.one_file_system.is_active(),
+            skip_file_systems: self
+                .skip_file_systems
+                .text()
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|fs_type| !fs_type.is_empty())
+                .map(ToOwned::to_owned)
+                .collect(),
+        })
     }
 
-    pub fn get_min_size(&self) -> u64 {
-        self.min_size.text().parse::<u64>().unwrap_or_default()
+    pub fn set_search_options(&self, options: &SearchOptions) {
+        fn index_of<T: PartialEq>(all: &[T], value: &T) -> u32 {
+            all.iter().position(|v| v == value).unwrap_or(0) as u32
+        }
+
+        self.directories.clear();
+        for path in &options.paths {
+            self.add_directory(path);
+        }
+        self.excluded.clear();
+        for exclusion in &options.exclude {
+            self.add_excluded(exclusion.clone());
+        }
+        self.max_depth
+            .set_value(options.max_depth.unwrap_or(0) as f64);
+        self.size.set_text(
+            &SizeRange {
+                min: options.min_size,
+                max: options.max_size,
+            }
+            .to_string(),
+        );
+        self.filter.set_text(
+            &options
+                .filter
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+        );
+        self.ignore_files.set_active(options.ignore_files);
+        self.find_directories.set_active(options.find_directories);
+        self.directory_similarity
+            .set_value(options.directory_similarity as f64);
+        self.similar_images.set_active(options.similar_images);
+        self.image_hash
+            .set_selected(index_of(&ImageHash::ALL, &options.image_hash));
+        self.image_distance.set_value(options.image_distance as f64);
+        self.audio_match
+            .set_selected(index_of(&AudioMatch::ALL, &options.audio_match));
+        self.ignore_metadata.set_active(options.ignore_metadata);
+        self.search_archives.set_active(options.search_archives);
+        self.symlinks
+            .set_selected(index_of(&SymlinkPolicy::ALL, &options.symlinks));
+        self.one_file_system.set_active(options.one_file_system);
+        self.skip_file_systems
+            .set_text(&options.skip_file_systems.join(", "));
     }
 }
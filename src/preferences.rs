@@ -0,0 +1,75 @@
+use crate::exclusion::DEFAULT_EXCLUDE_PATTERNS;
+use crate::find_duplicates::SearchOptions;
+use crate::gtk_prelude::*;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+const DEFAULT_WIDTH: i32 = 1200;
+const DEFAULT_HEIGHT: i32 = 800;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    pub search: SearchOptions,
+    pub confirm_delete: bool,
+    /// Results are updated when files change.
+    pub watch: bool,
+    pub window_width: i32,
+    pub window_height: i32,
+    pub maximized: bool,
+    /// Position of the divider between options and results.
+    pub sidebar_width: Option<i32>,
+}
+
+/// Options of the first launch: the current directory without usual junk.
+pub fn default_search_options() -> SearchOptions {
+    SearchOptions {
+        paths: std::env::current_dir().into_iter().collect(),
+        exclude: DEFAULT_EXCLUDE_PATTERNS.to_vec(),
+        ..SearchOptions::default()
+    }
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            search: default_search_options(),
+            confirm_delete: true,
+            watch: false,
+            window_width: DEFAULT_WIDTH,
+            window_height: DEFAULT_HEIGHT,
+            maximized: false,
+            sidebar_width: None,
+        }
+    }
+}
+
+/// Directory of the application in XDG config directory.
+pub fn config_dir() -> PathBuf {
+    glib::user_config_dir().join("find_duplicates")
+}
+
+fn preferences_path() -> PathBuf {
+    config_dir().join("preferences.json")
+}
+
+impl Preferences {
+    /// Reads saved preferences. Missing or broken file means defaults.
+    pub fn load() -> Self {
+        fs::read(preferences_path())
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = preferences_path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+}
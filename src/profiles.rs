@@ -0,0 +1,70 @@
+use crate::find_duplicates::SearchOptions;
+use crate::preferences::config_dir;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const EXTENSION: &str = "json";
+
+fn profiles_dir() -> PathBuf {
+    config_dir().join("profiles")
+}
+
+/// Name becomes a file name, so it cannot contain a path separator.
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        Err("Profile name is empty.".into())
+    } else if name.contains('/') || name.starts_with('.') {
+        Err(format!("\"{}\" is not a valid profile name.", name))
+    } else {
+        Ok(())
+    }
+}
+
+fn profile_path(name: &str) -> Result<PathBuf, String> {
+    validate_name(name)?;
+    Ok(profiles_dir().join(format!("{}.{}", name, EXTENSION)))
+}
+
+/// Names of saved profiles in alphabetical order.
+pub fn list() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(profiles_dir())
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == EXTENSION))
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_owned()))
+        .collect();
+    names.sort();
+    names
+}
+
+pub fn load(name: &str) -> Result<SearchOptions, Box<dyn Error>> {
+    let data = fs::read(profile_path(name)?)
+        .map_err(|error| format!("Cannot read profile \"{}\". {}", name, error))?;
+    let options = serde_json::from_slice(&data)
+        .map_err(|error| format!("Profile \"{}\" is broken. {}", name, error))?;
+    Ok(options)
+}
+
+/// Reads options from a file of the same format as a profile.
+pub fn load_file(path: &Path) -> Result<SearchOptions, Box<dyn Error>> {
+    let data =
+        fs::read(path).map_err(|error| format!("Cannot read {}. {}", path.display(), error))?;
+    let options = serde_json::from_slice(&data)
+        .map_err(|error| format!("{} is broken. {}", path.display(), error))?;
+    Ok(options)
+}
+
+pub fn save(name: &str, options: &SearchOptions) -> Result<(), Box<dyn Error>> {
+    let path = profile_path(name)?;
+    fs::create_dir_all(profiles_dir())?;
+    fs::write(path, serde_json::to_vec_pretty(options)?)?;
+    Ok(())
+}
+
+pub fn delete(name: &str) -> Result<(), Box<dyn Error>> {
+    fs::remove_file(profile_path(name)?)?;
+    Ok(())
+}
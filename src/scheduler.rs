@@ -0,0 +1,381 @@
+use crate::find_duplicates::{find_duplicate_groups, Cancellation, SearchOptions, SearchResults};
+use crate::gtk_prelude::*;
+use crate::main_window::MainWindow;
+use crate::preferences::config_dir;
+use crate::profiles;
+use humansize::{format_size, DECIMAL};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::{Rc, Weak};
+use std::thread;
+
+/// How often schedules are checked, in seconds.
+const CHECK_INTERVAL: u32 = 10 * 60;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Interval {
+    Daily,
+    Weekly,
+}
+
+impl Interval {
+    pub const ALL: [Self; 2] = [Self::Daily, Self::Weekly];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Daily => "Daily",
+            Self::Weekly => "Weekly",
+        }
+    }
+
+    fn seconds(self) -> i64 {
+        match self {
+            Self::Daily => 24 * 60 * 60,
+            Self::Weekly => 7 * 24 * 60 * 60,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Schedule {
+    pub profile: String,
+    pub interval: Interval,
+    /// A notification is shown when a run finds more new waste than this, in bytes.
+    pub threshold: u64,
+}
+
+/// Outcome of the last scheduled run of a profile.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RunRecord {
+    /// Unix time of the end of the run.
+    finished: i64,
+    /// Files which had duplicates.
+    duplicates: Vec<PathBuf>,
+}
+
+fn schedules_path() -> PathBuf {
+    config_dir().join("schedules.json")
+}
+
+fn record_path(profile: &str) -> PathBuf {
+    config_dir().join("runs").join(format!("{}.json", profile))
+}
+
+fn load_schedules() -> Vec<Schedule> {
+    fs::read(schedules_path())
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+fn save_schedules(schedules: &[Schedule]) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(config_dir())?;
+    fs::write(schedules_path(), serde_json::to_vec_pretty(schedules)?)?;
+    Ok(())
+}
+
+pub fn schedule_of(profile: &str) -> Option<Schedule> {
+    load_schedules()
+        .into_iter()
+        .find(|schedule| schedule.profile == profile)
+}
+
+/// Replaces the schedule of the profile. `None` stops scheduled runs of it. A run of the profile
+/// which is in progress is cancelled.
+pub fn set_schedule(profile: &str, schedule: Option<Schedule>) -> Result<(), Box<dyn Error>> {
+    let mut schedules = load_schedules();
+    schedules.retain(|schedule| schedule.profile != profile);
+    match schedule {
+        Some(schedule) => schedules.push(schedule),
+        None => {
+            let _ = fs::remove_file(record_path(profile));
+        }
+    }
+    save_schedules(&schedules)?;
+
+    SCHEDULER.with(|scheduler| {
+        if let Some(scheduler) = scheduler.borrow().upgrade() {
+            scheduler.cancel(Some(profile));
+            scheduler.update_hold(&schedules);
+        }
+    });
+    Ok(())
+}
+
+/// Keeps the application running without windows while there are schedules. It fails if there
+/// are none, as there would be nothing to run.
+pub fn run_in_background() -> Result<(), Box<dyn Error>> {
+    let held = SCHEDULER.with(|scheduler| {
+        let Some(scheduler) = scheduler.borrow().upgrade() else {
+            return false;
+        };
+        scheduler.update_hold(&load_schedules());
+        let held = scheduler.hold.borrow().is_some();
+        held
+    });
+    if held {
+        Ok(())
+    } else {
+        Err("No scans are scheduled, there is nothing to run in the background.".into())
+    }
+}
+
+fn load_record(profile: &str) -> Option<RunRecord> {
+    let data = fs::read(record_path(profile)).ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+fn save_record(profile: &str, record: &RunRecord) -> Result<(), Box<dyn Error>> {
+    let path = record_path(profile);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_vec_pretty(record)?)?;
+    Ok(())
+}
+
+fn is_due(schedule: &Schedule, now: i64) -> bool {
+    load_record(&schedule.profile)
+        .is_none_or(|record| now - record.finished >= schedule.interval.seconds())
+}
+
+/// Waste of files which were not duplicates in the previous run. The largest file of a group
+/// which is new as a whole is the one to keep, so it is not counted.
+fn new_waste(results: &SearchResults, previous: &HashSet<PathBuf>) -> u64 {
+    results
+        .groups
+        .iter()
+        .map(|group| {
+            let new: Vec<u64> = group
+                .files
+                .iter()
+                .filter(|fi| !previous.contains(&fi.path))
+                .map(|fi| fi.size)
+                .collect();
+            let waste: u64 = new.iter().sum();
+            if new.len() == group.files.len() {
+                waste - new.iter().max().copied().unwrap_or(0)
+            } else {
+                waste
+            }
+        })
+        .sum()
+}
+
+type RunResult = (String, SearchOptions, Result<SearchResults, String>);
+
+struct Scheduler {
+    app: glib::WeakRef<gtk::Application>,
+    sender: glib::Sender<RunResult>,
+    /// Profile which is being searched and cancellation of the search.
+    running: RefCell<Option<(String, Cancellation)>>,
+    /// Profiles whose runs failed since the last check. They are retried with the next check.
+    failed: RefCell<HashSet<String>>,
+    /// Keeps the application running without windows while there are schedules.
+    hold: RefCell<Option<gio::ApplicationHoldGuard>>,
+    /// Results of runs which have been notified about, by profile.
+    results: RefCell<HashMap<String, (SearchOptions, SearchResults)>>,
+}
+
+thread_local! {
+    /// Scheduler of the application, so changes of schedules reach it.
+    static SCHEDULER: RefCell<Weak<Scheduler>> = const { RefCell::new(Weak::new()) };
+}
+
+impl Scheduler {
+    /// Starts the first due run. Runs go one after another.
+    fn run_due(&self) {
+        if self.running.borrow().is_some() {
+            return;
+        }
+        let now = chrono::Utc::now().timestamp();
+        for schedule in load_schedules() {
+            if !is_due(&schedule, now) || self.failed.borrow().contains(&schedule.profile) {
+                continue;
+            }
+            let options = match profiles::load(&schedule.profile) {
+                Ok(options) => options,
+                Err(error) => {
+                    eprintln!("Scheduled run is skipped. {}", error);
+                    continue;
+                }
+            };
+            let cancellation = Cancellation::default();
+            *self.running.borrow_mut() = Some((schedule.profile.clone(), cancellation.clone()));
+            let sender = self.sender.clone();
+            thread::spawn(move || {
+                let results = find_duplicate_groups(&options, &cancellation)
+                    .map_err(|error| error.to_string());
+                let _ = sender.send((schedule.profile, options, results));
+            });
+            return;
+        }
+    }
+
+    fn on_run_finished(&self, (profile, options, results): RunResult) {
+        let cancelled = self
+            .running
+            .take()
+            .is_some_and(|(_, cancellation)| cancellation.is_cancelled());
+        let failed = match results {
+            _ if cancelled => false,
+            Ok(results) => match self.compare(&profile, options, results) {
+                Ok(()) => false,
+                Err(error) => {
+                    eprintln!("Cannot save results of profile \"{}\". {}", profile, error);
+                    true
+                }
+            },
+            Err(error) => {
+                eprintln!("Scheduled run of \"{}\" failed. {}", profile, error);
+                true
+            }
+        };
+        if failed {
+            // a run is due until its record is saved, so it would be restarted right away
+            self.failed.borrow_mut().insert(profile);
+        } else {
+            self.run_due();
+        }
+    }
+
+    fn check(&self) {
+        self.failed.borrow_mut().clear();
+        self.run_due();
+    }
+
+    /// Cancels the run in progress if it searches `profile`, or any run for `None`.
+    fn cancel(&self, profile: Option<&str>) {
+        if let Some((running, cancellation)) = &*self.running.borrow() {
+            if profile.is_none_or(|profile| profile == running) {
+                cancellation.cancel();
+            }
+        }
+    }
+
+    fn update_hold(&self, schedules: &[Schedule]) {
+        let mut hold = self.hold.borrow_mut();
+        if schedules.is_empty() {
+            *hold = None;
+        } else if hold.is_none() {
+            *hold = self.app.upgrade().map(|app| app.hold());
+        }
+    }
+
+    /// Notifies about new waste since the previous run.
+    fn compare(
+        &self,
+        profile: &str,
+        options: SearchOptions,
+        results: SearchResults,
+    ) -> Result<(), Box<dyn Error>> {
+        let previous: HashSet<PathBuf> = load_record(profile)
+            .map(|record| record.duplicates.into_iter().collect())
+            .unwrap_or_default();
+        let waste = new_waste(&results, &previous);
+        save_record(
+            profile,
+            &RunRecord {
+                finished: chrono::Utc::now().timestamp(),
+                duplicates: results
+                    .groups
+                    .iter()
+                    .flat_map(|group| group.files.iter().map(|fi| fi.path.clone()))
+                    .collect(),
+            },
+        )?;
+
+        let Some(schedule) = schedule_of(profile) else {
+            return Ok(());
+        };
+        let Some(app) = self.app.upgrade() else {
+            return Ok(());
+        };
+        if waste > schedule.threshold {
+            let notification = gio::Notification::new(&format!("Duplicates in \"{}\"", profile));
+            notification.set_body(Some(&format!(
+                "{} of new duplicates found",
+                format_size(waste, DECIMAL)
+            )));
+            notification.set_default_action_and_target_value(
+                "app.show-results",
+                Some(&profile.to_variant()),
+            );
+            app.send_notification(Some(&format!("scheduled-{}", profile)), &notification);
+            self.results
+                .borrow_mut()
+                .insert(profile.to_owned(), (options, results));
+        }
+        Ok(())
+    }
+
+    /// Opens a window with results of the last run. If they are gone, the profile is searched
+    /// again.
+    fn show_results(&self, profile: &str) {
+        let Some(app) = self.app.upgrade() else {
+            return;
+        };
+        let window = MainWindow::new(&app);
+        match self.results.borrow_mut().remove(profile) {
+            Some((options, results)) => {
+                window.show_results(options, results, profile.to_owned());
+                window.present();
+            }
+            None => match profiles::load(profile) {
+                Ok(options) => {
+                    window.set_search_options(&options, Some(profile.to_owned()));
+                    window.present();
+                    window.start_search();
+                }
+                Err(error) => {
+                    eprintln!("{}", error);
+                    window.destroy();
+                }
+            },
+        }
+    }
+}
+
+/// Runs scheduled profiles while the application is running and adds the `app.show-results`
+/// action, which is activated by notifications. While there are schedules the application keeps
+/// running after its last window is closed.
+pub fn start(app: &gtk::Application) {
+    let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_LOW);
+    let scheduler = Rc::new(Scheduler {
+        app: app.downgrade(),
+        sender,
+        running: RefCell::new(None),
+        failed: RefCell::new(HashSet::new()),
+        hold: RefCell::new(None),
+        results: RefCell::new(HashMap::new()),
+    });
+    SCHEDULER.with(|current| *current.borrow_mut() = Rc::downgrade(&scheduler));
+    scheduler.update_hold(&load_schedules());
+    app.connect_shutdown(clone!(@weak scheduler => move |_app| scheduler.cancel(None)));
+
+    receiver.attach(
+        None,
+        clone!(@weak scheduler => @default-return glib::Continue(false), move |result| {
+            scheduler.on_run_finished(result);
+            glib::Continue(true)
+        }),
+    );
+
+    let show_results = gio::SimpleAction::new("show-results", Some(glib::VariantTy::STRING));
+    show_results.connect_activate(clone!(@weak scheduler => move |_action, parameter| {
+        if let Some(profile) = parameter.and_then(|parameter| parameter.get::<String>()) {
+            scheduler.show_results(&profile);
+        }
+    }));
+    app.add_action(&show_results);
+
+    scheduler.run_due();
+    glib::timeout_add_seconds_local(CHECK_INTERVAL, move || {
+        scheduler.check();
+        glib::Continue(true)
+    });
+}
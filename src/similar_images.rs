@@ -0,0 +1,150 @@
+use crate::find_duplicates::{Cancellation, ContentCache, FileInfo};
+use image::imageops::FilterType;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::io;
+
+const IMAGE_EXTENSIONS: [&str; 5] = ["jpg", "jpeg", "png", "webp", "gif"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImageHash {
+    Average,
+    Difference,
+    Perceptual,
+}
+
+impl ImageHash {
+    pub const ALL: [ImageHash; 3] = [Self::Average, Self::Difference, Self::Perceptual];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Average => "aHash",
+            Self::Difference => "dHash",
+            Self::Perceptual => "pHash",
+        }
+    }
+}
+
+pub fn is_image(fi: &FileInfo) -> bool {
+    fi.path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            IMAGE_EXTENSIONS
+                .iter()
+                .any(|known| ext.eq_ignore_ascii_case(known))
+        })
+}
+
+fn grayscale(img: &image::DynamicImage, width: u32, height: u32) -> Vec<f64> {
+    img.resize_exact(width, height, FilterType::Triangle)
+        .to_luma8()
+        .pixels()
+        .map(|pixel| pixel.0[0] as f64)
+        .collect()
+}
+
+fn bits(values: impl Iterator<Item = bool>) -> u64 {
+    values.fold(0, |hash, bit| (hash << 1) | bit as u64)
+}
+
+fn average_hash(img: &image::DynamicImage) -> u64 {
+    let pixels = grayscale(img, 8, 8);
+    let mean = pixels.iter().sum::<f64>() / pixels.len() as f64;
+    bits(pixels.iter().map(|p| *p > mean))
+}
+
+fn difference_hash(img: &image::DynamicImage) -> u64 {
+    let pixels = grayscale(img, 9, 8);
+    bits(
+        pixels
+            .chunks(9)
+            .flat_map(|row| row.windows(2).map(|w| w[0] < w[1])),
+    )
+}
+
+fn dct_1d(input: &[f64]) -> Vec<f64> {
+    let n = input.len();
+    (0..n)
+        .map(|k| {
+            input
+                .iter()
+                .enumerate()
+                .map(|(i, x)| x * (PI / n as f64 * (i as f64 + 0.5) * k as f64).cos())
+                .sum()
+        })
+        .collect()
+}
+
+fn perceptual_hash(img: &image::DynamicImage) -> u64 {
+    const SIZE: usize = 32;
+    const LOW: usize = 8;
+
+    let pixels = grayscale(img, SIZE as u32, SIZE as u32);
+    let rows: Vec<Vec<f64>> = pixels.chunks(SIZE).map(dct_1d).collect();
+    let columns: Vec<Vec<f64>> = (0..LOW)
+        .map(|x| dct_1d(&rows.iter().map(|row| row[x]).collect::<Vec<_>>()))
+        .collect();
+
+    // low frequencies without DC coefficient
+    let low: Vec<f64> = (0..LOW)
+        .flat_map(|y| columns.iter().map(move |column| column[y]))
+        .collect();
+    let mut sorted: Vec<f64> = low[1..].to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let median = sorted[sorted.len() / 2];
+    bits(low.iter().map(|c| *c > median))
+}
+
+fn compute_hash(fi: &FileInfo, algorithm: ImageHash) -> Option<u64> {
+    let img = image::open(&fi.path).ok()?;
+    let hash = match algorithm {
+        ImageHash::Average => average_hash(&img),
+        ImageHash::Difference => difference_hash(&img),
+        ImageHash::Perceptual => perceptual_hash(&img),
+    };
+    Some(hash)
+}
+
+pub fn distance_to_similarity(distance: u32) -> u8 {
+    (100 - distance * 100 / 64) as u8
+}
+
+/// Groups images whose hashes are not farther than `max_distance` bits from the first (largest)
+/// image of a group. Files which cannot be decoded are ignored. Returns groups along with the
+/// largest distance within each group. Hashes are taken from `hashes` if the file did not change.
+pub fn group_similar_images(
+    mut images: Vec<FileInfo>,
+    algorithm: ImageHash,
+    max_distance: u32,
+    hashes: &mut ContentCache<Option<u64>>,
+    cancellation: &Cancellation,
+) -> io::Result<Vec<(Vec<FileInfo>, u32)>> {
+    images.sort_by_key(|fi| std::cmp::Reverse(fi.size));
+
+    let mut groups: Vec<(u64, Vec<FileInfo>, u32)> = Vec::new();
+    for fi in images {
+        cancellation.check()?;
+        let Some(hash) = hashes.get_or_compute(&fi, |fi| Ok(compute_hash(fi, algorithm)))? else {
+            continue;
+        };
+        let closest = groups
+            .iter_mut()
+            .map(|group| ((group.0 ^ hash).count_ones(), group))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min_by_key(|(distance, _)| *distance);
+        match closest {
+            Some((distance, group)) => {
+                group.1.push(fi);
+                group.2 = group.2.max(distance);
+            }
+            None => groups.push((hash, vec![fi], 0)),
+        }
+    }
+
+    Ok(groups
+        .into_iter()
+        .filter(|(_, files, _)| files.len() >= 2)
+        .map(|(_, files, distance)| (files, distance))
+        .collect())
+}
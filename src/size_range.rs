@@ -0,0 +1,291 @@
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SizeRange {
+    pub min: u64,
+    pub max: Option<u64>,
+}
+
+/// Formats the range in the syntax accepted by `parse_size_range`.
+impl std::fmt::Display for SizeRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.min, self.max) {
+            (min, None) => write!(f, "{}", min),
+            (0, Some(max)) => write!(f, "..{}", max),
+            (min, Some(max)) => write!(f, "{}..{}", min, max),
+        }
+    }
+}
+
+struct Size {
+    value: f64,
+    unit: u64,
+    /// Size is rounded up to units as `find -size` does.
+    rounded: bool,
+}
+
+impl Size {
+    fn bytes(&self) -> f64 {
+        self.value * self.unit as f64
+    }
+}
+
+fn unit(suffix: &str) -> Option<(u64, bool)> {
+    const KI: u64 = 1024;
+    let unit = match suffix {
+        "" => (1, false),
+        "c" => (1, true),
+        "w" => (2, true),
+        "b" => (512, true),
+        "k" => (KI, true),
+        "M" => (KI.pow(2), true),
+        "G" => (KI.pow(3), true),
+        _ => match suffix.to_lowercase().as_str() {
+            "b" => (1, false),
+            "kb" => (1000, false),
+            "mb" => (1000_u64.pow(2), false),
+            "gb" => (1000_u64.pow(3), false),
+            "tb" => (1000_u64.pow(4), false),
+            "kib" => (KI, false),
+            "mib" => (KI.pow(2), false),
+            "gib" => (KI.pow(3), false),
+            "tib" => (KI.pow(4), false),
+            _ => return None,
+        },
+    };
+    Some(unit)
+}
+
+fn parse_size(text: &str) -> Result<Size, String> {
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, suffix) = text.split_at(split);
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("\"{}\" is not a size.", text))?;
+    let (unit, rounded) =
+        unit(suffix.trim()).ok_or_else(|| format!("Unknown unit \"{}\".", suffix.trim()))?;
+    Ok(Size {
+        value,
+        unit,
+        rounded,
+    })
+}
+
+fn larger_than(size: &Size) -> u64 {
+    // sizes rounded up to units exceed N units when they are larger than N units in bytes
+    size.bytes().floor() as u64 + 1
+}
+
+fn smaller_than(size: &Size) -> Result<u64, String> {
+    let max = if size.rounded {
+        (size.value.ceil() - 1.0) * size.unit as f64
+    } else {
+        size.bytes().ceil() - 1.0
+    };
+    if max < 0.0 {
+        Err("No file is smaller than 0 bytes.".to_string())
+    } else {
+        Ok(max as u64)
+    }
+}
+
+fn parse_term(term: &str) -> Result<SizeRange, String> {
+    let range = if let Some(size) = term.strip_prefix('+') {
+        SizeRange {
+            min: larger_than(&parse_size(size)?),
+            max: None,
+        }
+    } else if let Some(size) = term.strip_prefix('-') {
+        SizeRange {
+            min: 0,
+            max: Some(smaller_than(&parse_size(size)?)?),
+        }
+    } else if let Some((min, max)) = term.split_once("..") {
+        SizeRange {
+            min: if min.is_empty() {
+                0
+            } else {
+                parse_size(min)?.bytes().ceil() as u64
+            },
+            max: if max.is_empty() {
+                None
+            } else {
+                Some(parse_size(max)?.bytes().floor() as u64)
+            },
+        }
+    } else {
+        SizeRange {
+            min: parse_size(term)?.bytes().ceil() as u64,
+            max: None,
+        }
+    };
+    Ok(range)
+}
+
+/// Splits the condition to terms. A unit separated from its number by spaces belongs to it, so
+/// `10 MB` is one term.
+fn terms(text: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        match terms.last_mut() {
+            Some(term)
+                if word.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && term.ends_with(|c: char| c.is_ascii_digit() || c == '.') =>
+            {
+                term.push_str(word)
+            }
+            _ => terms.push(word.to_owned()),
+        }
+    }
+    terms
+}
+
+/// Parses a file size condition. A condition is a list of whitespace separated terms, a file
+/// has to satisfy all of them:
+///
+/// * `+SIZE` — larger than SIZE,
+/// * `-SIZE` — smaller than SIZE,
+/// * `MIN..MAX`, `MIN..`, `..MAX` — within the range, bounds are inclusive,
+/// * `SIZE` — at least SIZE.
+///
+/// SIZE is a number followed by an optional unit. Suffixes of `find -size` (`c`, `w`, `b`, `k`,
+/// `M`, `G`) work as in find, i.e. a size is rounded up to units before comparison, so `-1k`
+/// matches empty files only. Other units are compared exactly: decimal (`B`, `kB`, `MB`, `GB`,
+/// `TB`) and binary ones (`KiB`, `MiB`, `GiB`, `TiB`). A number without unit is a number of bytes.
+pub fn parse_size_range(text: &str) -> Result<SizeRange, String> {
+    let mut range = SizeRange { min: 0, max: None };
+    for term in terms(text) {
+        let term = parse_term(&term)?;
+        range.min = range.min.max(term.min);
+        range.max = match (range.max, term.max) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+    }
+    if range.max.is_some_and(|max| max < range.min) {
+        return Err(format!("No file matches size \"{}\".", text.trim()));
+    }
+    Ok(range)
+}
+
+/// Parses a single size, e.g. `100MB`, to a number of bytes. Units are the ones of
+/// `parse_size_range`.
+pub fn parse_bytes(text: &str) -> Result<u64, String> {
+    Ok(parse_size(text.trim())?.bytes().ceil() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(min: u64, max: Option<u64>) -> SizeRange {
+        SizeRange { min, max }
+    }
+
+    #[test]
+    fn find_units_are_rounded_up() {
+        assert_eq!(parse_size_range("+1M"), Ok(range(1024 * 1024 + 1, None)));
+        assert_eq!(parse_size_range("-1k"), Ok(range(0, Some(0))));
+        assert_eq!(parse_size_range("-2k"), Ok(range(0, Some(1024))));
+        assert_eq!(parse_size_range("-1G"), Ok(range(0, Some(0))));
+        assert_eq!(parse_size_range("+0"), Ok(range(1, None)));
+    }
+
+    #[test]
+    fn block_units() {
+        assert_eq!(parse_size_range("-100c"), Ok(range(0, Some(99))));
+        assert_eq!(parse_size_range("+2w"), Ok(range(5, None)));
+        assert_eq!(parse_size_range("-3b"), Ok(range(0, Some(1024))));
+        assert_eq!(parse_size_range("-1b"), Ok(range(0, Some(0))));
+    }
+
+    #[test]
+    fn decimal_and_binary_units_are_exact() {
+        assert_eq!(parse_size_range("100"), Ok(range(100, None)));
+        assert_eq!(parse_size_range("1B"), Ok(range(1, None)));
+        assert_eq!(parse_size_range("1.5kB"), Ok(range(1500, None)));
+        assert_eq!(parse_size_range("2mb"), Ok(range(2_000_000, None)));
+        assert_eq!(parse_size_range("1TB"), Ok(range(1_000_000_000_000, None)));
+        assert_eq!(parse_size_range("1KiB"), Ok(range(1024, None)));
+        assert_eq!(
+            parse_size_range("1GiB"),
+            Ok(range(1024 * 1024 * 1024, None))
+        );
+        assert_eq!(parse_size_range("-0.5kB"), Ok(range(0, Some(499))));
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(
+            parse_size_range("1kB..2MB"),
+            Ok(range(1000, Some(2_000_000)))
+        );
+        assert_eq!(parse_size_range("..1KiB"), Ok(range(0, Some(1024))));
+        assert_eq!(parse_size_range("1MiB.."), Ok(range(1024 * 1024, None)));
+        assert_eq!(parse_size_range(""), Ok(range(0, None)));
+    }
+
+    #[test]
+    fn terms_are_combined() {
+        assert_eq!(
+            parse_size_range("+1k -2M"),
+            Ok(range(1025, Some(1024 * 1024)))
+        );
+        assert_eq!(
+            parse_size_range("1kB..1MB 10kB"),
+            Ok(range(10_000, Some(1_000_000)))
+        );
+    }
+
+    #[test]
+    fn units_can_be_separated_by_space() {
+        assert_eq!(parse_size_range("10 MB"), Ok(range(10_000_000, None)));
+        assert_eq!(
+            parse_size_range("1 MB..2 MB"),
+            Ok(range(1_000_000, Some(2_000_000)))
+        );
+        assert_eq!(parse_size_range("+1 k"), Ok(range(1025, None)));
+    }
+
+    #[test]
+    fn invalid_conditions_are_rejected() {
+        assert_eq!(
+            parse_size_range("abc"),
+            Err("\"abc\" is not a size.".to_string())
+        );
+        assert_eq!(
+            parse_size_range("10XB"),
+            Err("Unknown unit \"XB\".".to_string())
+        );
+        assert_eq!(
+            parse_size_range("10 XB"),
+            Err("Unknown unit \"XB\".".to_string())
+        );
+        assert!(parse_size_range("1.2.3").is_err());
+        assert!(parse_size_range("+").is_err());
+        assert_eq!(
+            parse_size_range("-0"),
+            Err("No file is smaller than 0 bytes.".to_string())
+        );
+        assert_eq!(
+            parse_size_range("+1k -1M"),
+            Err("No file matches size \"+1k -1M\".".to_string())
+        );
+    }
+
+    #[test]
+    fn bytes() {
+        assert_eq!(parse_bytes("100MB"), Ok(100_000_000));
+        assert_eq!(parse_bytes(" 1 GiB "), Ok(1024 * 1024 * 1024));
+        assert_eq!(parse_bytes("1k"), Ok(1024));
+        assert!(parse_bytes("-100MB").is_err());
+        assert!(parse_bytes("").is_err());
+    }
+
+    #[test]
+    fn display_is_parsed_back() {
+        for text in ["100", "..5", "10..20"] {
+            assert_eq!(parse_size_range(text).unwrap().to_string(), text);
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use crate::find_duplicates::{FileInfo, SearchResults};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Number of bars of charts which are not split into fixed buckets.
+const TOP: usize = 10;
+
+const KB: u64 = 1000;
+const MB: u64 = 1000 * KB;
+const GB: u64 = 1000 * MB;
+
+const SIZE_BUCKETS: [(u64, Option<u64>, &str); 6] = [
+    (0, Some(KB), "Under 1 KB"),
+    (KB, Some(MB), "1 KB – 1 MB"),
+    (MB, Some(10 * MB), "1 MB – 10 MB"),
+    (10 * MB, Some(100 * MB), "10 MB – 100 MB"),
+    (100 * MB, Some(GB), "100 MB – 1 GB"),
+    (GB, None, "Over 1 GB"),
+];
+
+const AGE_BUCKETS: [(u64, Option<u64>, &str); 6] = [
+    (0, Some(7), "Last week"),
+    (7, Some(30), "Last month"),
+    (30, Some(182), "Last 6 months"),
+    (182, Some(365), "Last year"),
+    (365, Some(5 * 365), "Last 5 years"),
+    (5 * 365, None, "Older"),
+];
+
+/// Titles of charts in the order of `Statistics::charts`.
+pub const CHART_TITLES: [&str; 5] = [
+    "By directory",
+    "By extension",
+    "By size",
+    "By age",
+    "Largest groups",
+];
+
+/// Files a bar of a chart stands for.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Category {
+    /// Files under the top-level directory of a searched path. Files right in the searched path
+    /// belong to the searched path itself.
+    Directory(PathBuf),
+    /// Files with the lowercase extension. Empty for files without one.
+    Extension(String),
+    /// Files at least `min` and less than `max` bytes in size.
+    Size { min: u64, max: Option<u64> },
+    /// Files last modified at least `min` and less than `max` days ago.
+    Age { min: u64, max: Option<u64> },
+    /// Files of the group at the index of `SearchResults::groups`.
+    Group(usize),
+}
+
+pub struct Bar {
+    pub label: String,
+    /// Size of redundant copies, in bytes.
+    pub waste: u64,
+    pub category: Category,
+}
+
+pub struct Chart {
+    pub title: &'static str,
+    pub bars: Vec<Bar>,
+}
+
+/// Waste of search results broken down in several ways. Only redundant copies are counted, so
+/// bars of charts by size and by age add up to the total waste. Other charts show only the
+/// largest categories.
+pub struct Statistics {
+    roots: Vec<PathBuf>,
+    now: SystemTime,
+    pub charts: Vec<Chart>,
+}
+
+fn top_directory(path: &Path, roots: &[PathBuf]) -> PathBuf {
+    let root = roots
+        .iter()
+        .filter(|root| path.starts_with(root))
+        .max_by_key(|root| root.components().count());
+    let Some(root) = root else {
+        return path.parent().map(Path::to_path_buf).unwrap_or_default();
+    };
+    let mut rest = path.strip_prefix(root).unwrap_or(path).components();
+    match (rest.next(), rest.next()) {
+        (Some(first), Some(_)) => root.join(first),
+        _ => root.clone(),
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+fn age_in_days(fi: &FileInfo, now: SystemTime) -> u64 {
+    now.duration_since(fi.modified)
+        .unwrap_or(Duration::ZERO)
+        .as_secs()
+        / (24 * 60 * 60)
+}
+
+fn in_range(value: u64, min: u64, max: Option<u64>) -> bool {
+    value >= min && max.is_none_or(|max| value < max)
+}
+
+/// Bars of the largest categories, largest first.
+fn top_bars(waste: HashMap<Category, u64>, label: impl Fn(&Category) -> String) -> Vec<Bar> {
+    let mut bars: Vec<Bar> = waste
+        .into_iter()
+        .filter(|(_, waste)| *waste > 0)
+        .map(|(category, waste)| Bar {
+            label: label(&category),
+            waste,
+            category,
+        })
+        .collect();
+    bars.sort_by(|a, b| b.waste.cmp(&a.waste).then_with(|| a.label.cmp(&b.label)));
+    bars.truncate(TOP);
+    bars
+}
+
+/// Bars of all buckets in their order, empty ones included.
+fn bucket_bars(
+    buckets: &[(u64, Option<u64>, &str)],
+    category: impl Fn(u64, Option<u64>) -> Category,
+    value: impl Fn(&FileInfo) -> u64,
+    files: &[&FileInfo],
+) -> Vec<Bar> {
+    buckets
+        .iter()
+        .map(|&(min, max, label)| Bar {
+            label: label.to_owned(),
+            waste: files
+                .iter()
+                .filter(|fi| in_range(value(fi), min, max))
+                .map(|fi| fi.freed().logical)
+                .sum(),
+            category: category(min, max),
+        })
+        .collect()
+}
+
+impl Statistics {
+    pub fn new(results: &SearchResults, roots: &[PathBuf]) -> Self {
+        let now = SystemTime::now();
+        let redundant: Vec<&FileInfo> = results
+            .groups
+            .iter()
+            .flat_map(|group| group.redundant())
+            .collect();
+
+        let mut by_directory = HashMap::new();
+        let mut by_extension = HashMap::new();
+        for fi in &redundant {
+            *by_directory
+                .entry(Category::Directory(top_directory(&fi.path, roots)))
+                .or_insert(0) += fi.freed().logical;
+            *by_extension
+                .entry(Category::Extension(extension(&fi.path)))
+                .or_insert(0) += fi.freed().logical;
+        }
+        let by_group = results
+            .groups
+            .iter()
+            .enumerate()
+            .map(|(index, group)| (Category::Group(index), group.waste().logical))
+            .collect();
+
+        let charts = vec![
+            Chart {
+                title: CHART_TITLES[0],
+                bars: top_bars(by_directory, |category| match category {
+                    Category::Directory(dir) => dir.display().to_string(),
+                    _ => String::new(),
+                }),
+            },
+            Chart {
+                title: CHART_TITLES[1],
+                bars: top_bars(by_extension, |category| match category {
+                    Category::Extension(ext) if ext.is_empty() => "(no extension)".to_owned(),
+                    Category::Extension(ext) => format!(".{}", ext),
+                    _ => String::new(),
+                }),
+            },
+            Chart {
+                title: CHART_TITLES[2],
+                bars: bucket_bars(
+                    &SIZE_BUCKETS,
+                    |min, max| Category::Size { min, max },
+                    |fi| fi.size,
+                    &redundant,
+                ),
+            },
+            Chart {
+                title: CHART_TITLES[3],
+                bars: bucket_bars(
+                    &AGE_BUCKETS,
+                    |min, max| Category::Age { min, max },
+                    |fi| age_in_days(fi, now),
+                    &redundant,
+                ),
+            },
+            Chart {
+                title: CHART_TITLES[4],
+                bars: top_bars(by_group, |category| match category {
+                    Category::Group(index) => {
+                        let group = &results.groups[*index];
+                        let name = group.files[0]
+                            .path
+                            .file_name()
+                            .map(|name| name.to_string_lossy().into_owned())
+                            .unwrap_or_default();
+                        format!("{} × {}", name, group.files.len())
+                    }
+                    _ => String::new(),
+                }),
+            },
+        ];
+
+        Self {
+            roots: roots.to_vec(),
+            now,
+            charts,
+        }
+    }
+
+    fn matches(&self, category: &Category, group: usize, fi: &FileInfo) -> bool {
+        match category {
+            Category::Directory(dir) => top_directory(&fi.path, &self.roots) == *dir,
+            Category::Extension(ext) => extension(&fi.path) == *ext,
+            Category::Size { min, max } => in_range(fi.size, *min, *max),
+            Category::Age { min, max } => in_range(age_in_days(fi, self.now), *min, *max),
+            Category::Group(index) => group == *index,
+        }
+    }
+
+    /// Paths of files of the category, including other hard links of them.
+    pub fn matching_paths(
+        &self,
+        results: &SearchResults,
+        category: &Category,
+    ) -> BTreeSet<PathBuf> {
+        let mut paths = BTreeSet::new();
+        for (index, group) in results.groups.iter().enumerate() {
+            for fi in &group.files {
+                if self.matches(category, index, fi) {
+                    paths.insert(fi.path.clone());
+                    paths.extend(fi.hard_links.iter().cloned());
+                }
+            }
+        }
+        paths
+    }
+}
+
+/// Tells whether the path or anything under it is in `paths`.
+pub fn contains_under(paths: &BTreeSet<PathBuf>, path: &Path) -> bool {
+    paths
+        .range(path.to_path_buf()..)
+        .next()
+        .is_some_and(|first| first.starts_with(path))
+}
@@ -0,0 +1,286 @@
+use crate::find_duplicates::SearchResults;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Height of the name of a directory above its content.
+pub const HEADER_HEIGHT: f64 = 16.0;
+/// Space between borders of a directory and its content.
+const PADDING: f64 = 2.0;
+/// Cells smaller than this are not split further.
+const MIN_SIZE: f64 = 4.0;
+
+/// Directory or a redundant copy of a file. Directories are sized by redundant copies inside.
+/// An archive which is a redundant copy with redundant members contains both.
+#[derive(Debug)]
+pub struct Node {
+    pub name: String,
+    pub path: PathBuf,
+    pub size: u64,
+    /// Index of the group in `SearchResults::groups`. Set for files only.
+    pub group: Option<usize>,
+    /// Largest first.
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    fn collect_files<'a>(&'a self, files: &mut Vec<&'a Path>) {
+        if self.group.is_some() {
+            files.push(&self.path);
+        }
+        for child in &self.children {
+            child.collect_files(files);
+        }
+    }
+
+    /// Files of the node and under it.
+    pub fn files(&self) -> Vec<&Path> {
+        let mut files = Vec::new();
+        self.collect_files(&mut files);
+        files
+    }
+}
+
+#[derive(Default)]
+struct Tree {
+    children: BTreeMap<OsString, Tree>,
+    file: Option<(u64, usize)>,
+}
+
+impl Tree {
+    fn insert(&mut self, rest: &Path, size: u64, group: usize) {
+        let mut components = rest.iter();
+        match components.next() {
+            Some(first) => self.children.entry(first.to_owned()).or_default().insert(
+                components.as_path(),
+                size,
+                group,
+            ),
+            None => self.file = Some((size, group)),
+        }
+    }
+
+    fn into_node(self, name: String, path: PathBuf) -> Node {
+        let mut children: Vec<Node> = self
+            .children
+            .into_iter()
+            .map(|(name, tree)| {
+                let path = path.join(&name);
+                tree.into_node(name.to_string_lossy().into_owned(), path)
+            })
+            .filter(|node| node.size > 0)
+            .collect();
+        nest_archive_members(&mut children);
+        children.sort_by_key(|node| Reverse(node.size));
+        let (size, group) = match self.file {
+            Some((size, group)) => (size, Some(group)),
+            None => (children.iter().map(|child| child.size).sum(), None),
+        };
+        Node {
+            name,
+            path,
+            size,
+            group,
+            children,
+        }
+    }
+}
+
+/// Moves members of archives which are redundant copies themselves (`archive!/member` paths)
+/// into nodes of the archives, along with the copy of the archive, so an archive is sized by
+/// both.
+fn nest_archive_members(children: &mut Vec<Node>) {
+    let (members, rest): (Vec<Node>, Vec<Node>) = std::mem::take(children)
+        .into_iter()
+        .partition(|node| node.group.is_none() && node.name.ends_with('!'));
+    *children = rest;
+    for members in members {
+        let archive = members.name.strip_suffix('!').unwrap_or(&members.name);
+        let Some(node) = children
+            .iter_mut()
+            .find(|node| node.group.is_some() && node.name == archive)
+        else {
+            children.push(members);
+            continue;
+        };
+        let copy = Node {
+            name: node.name.clone(),
+            path: node.path.clone(),
+            size: node.size,
+            group: node.group.take(),
+            children: Vec::new(),
+        };
+        node.size += members.size;
+        node.children = members.children;
+        node.children.push(copy);
+        node.children.sort_by_key(|node| Reverse(node.size));
+    }
+}
+
+/// Hierarchy of redundant copies of files under the searched paths, one node per path. Files
+/// outside of them are put under the root directory.
+pub fn build(results: &SearchResults, roots: &[PathBuf]) -> Vec<Node> {
+    let mut trees: BTreeMap<PathBuf, Tree> = BTreeMap::new();
+    for (index, group) in results.groups.iter().enumerate() {
+        for fi in group.redundant() {
+            let root = roots
+                .iter()
+                .filter(|root| fi.path.starts_with(root))
+                .max_by_key(|root| root.components().count())
+                .cloned()
+                .unwrap_or_else(|| PathBuf::from("/"));
+            let rest = fi.path.strip_prefix(&root).unwrap_or(&fi.path);
+            trees
+                .entry(root)
+                .or_default()
+                .insert(rest, fi.freed().logical, index);
+        }
+    }
+    let mut nodes: Vec<Node> = trees
+        .into_iter()
+        .map(|(root, tree)| tree.into_node(root.display().to_string(), root))
+        .filter(|node| node.size > 0)
+        .collect();
+    nodes.sort_by_key(|node| Reverse(node.size));
+    nodes
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+#[derive(Debug)]
+pub struct Cell {
+    pub rect: Rect,
+    /// Positions of the node and of its ancestors among their siblings, the top one first.
+    pub node: Vec<usize>,
+    pub depth: usize,
+}
+
+impl Cell {
+    /// Node of the cell in nodes which were laid out.
+    pub fn node<'n>(&self, nodes: &'n [Node]) -> &'n Node {
+        let (first, rest) = self.node.split_first().unwrap();
+        rest.iter()
+            .fold(&nodes[*first], |node, index| &node.children[*index])
+    }
+}
+
+/// The worst aspect ratio of a row of areas laid along a side of the length.
+fn worst_ratio(sum: f64, max: f64, min: f64, side: f64) -> f64 {
+    let side2 = side * side;
+    let sum2 = sum * sum;
+    (side2 * max / sum2).max(sum2 / (side2 * min))
+}
+
+/// Splits the rectangle into ones of the areas, keeping them close to squares. Areas are
+/// expected in descending order and to add up to the area of the rectangle.
+fn squarify(areas: &[f64], mut rect: Rect) -> Vec<Rect> {
+    let mut rects = Vec::with_capacity(areas.len());
+    let mut start = 0;
+    while start < areas.len() {
+        let side = rect.width.min(rect.height);
+        let largest = areas[start];
+        let mut sum = largest;
+        let mut worst = worst_ratio(sum, largest, largest, side);
+        let mut end = start + 1;
+        while end < areas.len() {
+            let next = worst_ratio(sum + areas[end], largest, areas[end], side);
+            if next > worst {
+                break;
+            }
+            sum += areas[end];
+            worst = next;
+            end += 1;
+        }
+
+        let row = &areas[start..end];
+        if rect.width >= rect.height {
+            let width = if rect.height > 0.0 {
+                sum / rect.height
+            } else {
+                0.0
+            };
+            let mut y = rect.y;
+            for area in row {
+                let height = if width > 0.0 { area / width } else { 0.0 };
+                rects.push(Rect {
+                    x: rect.x,
+                    y,
+                    width,
+                    height,
+                });
+                y += height;
+            }
+            rect.x += width;
+            rect.width -= width;
+        } else {
+            let height = if rect.width > 0.0 {
+                sum / rect.width
+            } else {
+                0.0
+            };
+            let mut x = rect.x;
+            for area in row {
+                let width = if height > 0.0 { area / height } else { 0.0 };
+                rects.push(Rect {
+                    x,
+                    y: rect.y,
+                    width,
+                    height,
+                });
+                x += width;
+            }
+            rect.y += height;
+            rect.height -= height;
+        }
+        start = end;
+    }
+    rects
+}
+
+fn layout_nodes(nodes: &[Node], rect: Rect, parent: &[usize], cells: &mut Vec<Cell>) {
+    let total: u64 = nodes.iter().map(|node| node.size).sum();
+    if total == 0 || rect.width < MIN_SIZE || rect.height < MIN_SIZE {
+        return;
+    }
+    let scale = rect.width * rect.height / total as f64;
+    let areas: Vec<f64> = nodes.iter().map(|node| node.size as f64 * scale).collect();
+    for (index, (node, rect)) in nodes.iter().zip(squarify(&areas, rect)).enumerate() {
+        let mut path = parent.to_vec();
+        path.push(index);
+        cells.push(Cell {
+            rect,
+            node: path.clone(),
+            depth: parent.len(),
+        });
+        if !node.children.is_empty() {
+            let content = Rect {
+                x: rect.x + PADDING,
+                y: rect.y + HEADER_HEIGHT,
+                width: rect.width - 2.0 * PADDING,
+                height: rect.height - HEADER_HEIGHT - PADDING,
+            };
+            layout_nodes(&node.children, content, &path, cells);
+        }
+    }
+}
+
+/// Cells of nodes within the rectangle. Directories come before their content, so cells may be
+/// drawn in order. Cells too small to be seen are left out.
+pub fn layout(nodes: &[Node], rect: Rect) -> Vec<Cell> {
+    let mut cells = Vec::new();
+    layout_nodes(nodes, rect, &[], &mut cells);
+    cells
+}
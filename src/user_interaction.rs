@@ -62,6 +62,165 @@ pub async fn prompt(
     result
 }
 
+pub struct PatternAnswer {
+    pub pattern: String,
+    pub case_sensitive: bool,
+    pub basename: bool,
+}
+
+/// Asks for a pattern and how to match it. Ok is available only when `validate` accepts the
+/// pattern, otherwise the error is shown under the entry.
+pub async fn prompt_pattern(
+    parent: &gtk::Window,
+    title: &str,
+    validate: impl Fn(&str) -> Result<(), String> + 'static,
+) -> Option<PatternAnswer> {
+    let dlg = dialog(parent, title);
+
+    dlg.add_button("Cancel", gtk::ResponseType::Cancel);
+    dlg.add_button("Ok", gtk::ResponseType::Ok);
+    dlg.set_default_response(gtk::ResponseType::Ok);
+    dlg.set_response_sensitive(gtk::ResponseType::Ok, false);
+
+    let container = gtk::Box::builder()
+        .homogeneous(false)
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(8)
+        .margin_start(20)
+        .margin_end(20)
+        .margin_top(20)
+        .margin_bottom(20)
+        .build();
+    container.set_parent(&dlg.content_area());
+
+    let label = gtk::Label::builder()
+        .label("Pattern:")
+        .xalign(0.0_f32)
+        .yalign(0.5_f32)
+        .build();
+    container.append(&label);
+
+    let entry = gtk::Entry::builder().activates_default(true).build();
+    container.append(&entry);
+
+    let error = gtk::Label::builder()
+        .xalign(0.0_f32)
+        .yalign(0.5_f32)
+        .wrap(true)
+        .visible(false)
+        .build();
+    error.add_css_class("error");
+    container.append(&error);
+
+    let case_sensitive = gtk::CheckButton::builder()
+        .label("Case sensitive")
+        .active(true)
+        .build();
+    container.append(&case_sensitive);
+
+    let basename = gtk::CheckButton::builder()
+        .label("Match file name only")
+        .active(false)
+        .build();
+    container.append(&basename);
+
+    entry.connect_changed(clone!(@weak dlg, @weak error => move |entry| {
+        let text = entry.text();
+        let result = if text.is_empty() {
+            Err(String::new())
+        } else {
+            validate(&text)
+        };
+        dlg.set_response_sensitive(gtk::ResponseType::Ok, result.is_ok());
+        let message = result.err().unwrap_or_default();
+        error.set_visible(!message.is_empty());
+        error.set_label(&message);
+    }));
+
+    dlg.show();
+    let result = match dlg.run_future().await {
+        gtk::ResponseType::Ok => Some(PatternAnswer {
+            pattern: entry.text().to_string(),
+            case_sensitive: case_sensitive.is_active(),
+            basename: basename.is_active(),
+        }),
+        _ => None,
+    };
+    dlg.close();
+    pending().await;
+    result
+}
+
+pub struct ScheduleAnswer {
+    /// Index of the chosen interval, `None` for no schedule.
+    pub interval: Option<usize>,
+    pub threshold: String,
+}
+
+/// Asks how often to run a scan and how much new waste is worth a notification.
+pub async fn prompt_schedule(
+    parent: &gtk::Window,
+    title: &str,
+    intervals: &[&str],
+    interval: Option<usize>,
+    threshold: &str,
+) -> Option<ScheduleAnswer> {
+    let dlg = dialog(parent, title);
+
+    dlg.add_button("Cancel", gtk::ResponseType::Cancel);
+    dlg.add_button("Ok", gtk::ResponseType::Ok);
+    dlg.set_default_response(gtk::ResponseType::Ok);
+
+    let container = gtk::Box::builder()
+        .homogeneous(false)
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(8)
+        .margin_start(20)
+        .margin_end(20)
+        .margin_top(20)
+        .margin_bottom(20)
+        .build();
+    container.set_parent(&dlg.content_area());
+
+    let label = gtk::Label::builder()
+        .label("Run:")
+        .xalign(0.0_f32)
+        .yalign(0.5_f32)
+        .build();
+    container.append(&label);
+
+    let mut choices = vec!["Never"];
+    choices.extend_from_slice(intervals);
+    let dropdown = gtk::DropDown::from_strings(&choices);
+    dropdown.set_selected(interval.map_or(0, |index| index + 1) as u32);
+    container.append(&dropdown);
+
+    let label = gtk::Label::builder()
+        .label("Notify when new duplicates waste more than:")
+        .xalign(0.0_f32)
+        .yalign(0.5_f32)
+        .build();
+    container.append(&label);
+
+    let entry = gtk::Entry::builder()
+        .text(threshold)
+        .activates_default(true)
+        .build();
+    container.append(&entry);
+
+    dlg.show();
+    let result = match dlg.run_future().await {
+        gtk::ResponseType::Ok => Some(ScheduleAnswer {
+            interval: (dropdown.selected() as usize).checked_sub(1),
+            threshold: entry.text().to_string(),
+        }),
+        _ => None,
+    };
+    dlg.close();
+    pending().await;
+    result
+}
+
 pub async fn confirm_delete(parent: &gtk::Window, message: &str) -> (bool, bool) {
     let dlg = dialog(parent, "Delete");
     let yes = dlg.add_button("Delete", gtk::ResponseType::Ok);
@@ -178,7 +337,8 @@ pub struct ProgressDialog {
 }
 
 impl ProgressDialog {
-    pub fn new(parent: &gtk::Window, title: &str) -> Self {
+    /// `on_cancel` is called when the user presses Cancel. The dialog stays open until `close`.
+    pub fn new(parent: &gtk::Window, title: &str, on_cancel: impl Fn() + 'static) -> Self {
         let dlg = gtk::Dialog::builder()
             .title(title)
             .transient_for(parent)
@@ -200,6 +360,14 @@ impl ProgressDialog {
 
         progress_bar.set_parent(&dlg.content_area());
 
+        dlg.add_button("Cancel", gtk::ResponseType::Cancel);
+        dlg.connect_response(move |dlg, response| {
+            if response == gtk::ResponseType::Cancel {
+                dlg.set_response_sensitive(gtk::ResponseType::Cancel, false);
+                on_cancel();
+            }
+        });
+
         let running = Rc::new(Cell::new(true));
         dlg.connect_close_request(
             clone!(@weak running => @default-return glib::signal::Inhibit(false), move |_dlg| {
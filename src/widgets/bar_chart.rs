@@ -0,0 +1,134 @@
+use crate::gtk_prelude::*;
+use humansize::{format_size, DECIMAL};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const ROW_HEIGHT: f64 = 24.0;
+const BAR_HEIGHT: f64 = 16.0;
+const FONT_SIZE: f64 = 12.0;
+const PADDING: f64 = 8.0;
+const MAX_LABEL_WIDTH: f64 = 240.0;
+const VALUE_WIDTH: f64 = 80.0;
+const BAR_COLOR: (f64, f64, f64) = (0.21, 0.52, 0.89);
+
+/// Horizontal bar chart of sizes in bytes. Each bar is labeled with its name on the left and
+/// its size on the right.
+#[derive(Clone, Debug)]
+pub struct BarChart {
+    area: gtk::DrawingArea,
+    bars: Rc<RefCell<Vec<(String, u64)>>>,
+}
+
+fn bar_at(bars: &[(String, u64)], y: f64) -> Option<usize> {
+    let index = (y / ROW_HEIGHT) as usize;
+    (y >= 0.0 && index < bars.len()).then_some(index)
+}
+
+fn set_source_color(cr: &gtk::cairo::Context, color: &gdk::RGBA) {
+    cr.set_source_rgba(
+        color.red() as f64,
+        color.green() as f64,
+        color.blue() as f64,
+        color.alpha() as f64,
+    );
+}
+
+fn draw(
+    area: &gtk::DrawingArea,
+    cr: &gtk::cairo::Context,
+    width: i32,
+    bars: &[(String, u64)],
+) -> Result<(), gtk::cairo::Error> {
+    let width = width as f64;
+    let label_width = (width * 0.35).min(MAX_LABEL_WIDTH);
+    let bar_start = label_width + PADDING;
+    let bar_space = (width - bar_start - VALUE_WIDTH).max(0.0);
+    let max = bars
+        .iter()
+        .map(|(_, value)| *value)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let color = area.style_context().color();
+    cr.set_font_size(FONT_SIZE);
+    for (index, (label, value)) in bars.iter().enumerate() {
+        let top = index as f64 * ROW_HEIGHT;
+        let baseline = top + (ROW_HEIGHT + FONT_SIZE) / 2.0 - 2.0;
+
+        cr.save()?;
+        cr.rectangle(0.0, top, label_width, ROW_HEIGHT);
+        cr.clip();
+        set_source_color(cr, &color);
+        cr.move_to(0.0, baseline);
+        cr.show_text(label)?;
+        cr.restore()?;
+
+        let length = bar_space * (*value as f64) / (max as f64);
+        cr.set_source_rgb(BAR_COLOR.0, BAR_COLOR.1, BAR_COLOR.2);
+        cr.rectangle(
+            bar_start,
+            top + (ROW_HEIGHT - BAR_HEIGHT) / 2.0,
+            length,
+            BAR_HEIGHT,
+        );
+        cr.fill()?;
+
+        set_source_color(cr, &color);
+        cr.move_to(bar_start + length + PADDING / 2.0, baseline);
+        cr.show_text(&format_size(*value, DECIMAL))?;
+    }
+    Ok(())
+}
+
+impl BarChart {
+    /// `on_click` gets the index of the clicked bar.
+    pub fn new(on_click: impl Fn(usize) + 'static) -> Self {
+        let area = gtk::DrawingArea::builder()
+            .hexpand(true)
+            .has_tooltip(true)
+            .build();
+        area.set_cursor_from_name(Some("pointer"));
+        let bars: Rc<RefCell<Vec<(String, u64)>>> = Rc::new(RefCell::new(Vec::new()));
+
+        area.set_draw_func(clone!(@strong bars => move |area, cr, width, _height| {
+            if let Err(error) = draw(area, cr, width, &bars.borrow()) {
+                eprintln!("Cannot draw a chart. {}", error);
+            }
+        }));
+
+        area.connect_query_tooltip(
+            clone!(@strong bars => move |_area, _x, y, _keyboard, tooltip| {
+                let bars = bars.borrow();
+                let Some(index) = bar_at(&bars, y as f64) else {
+                    return false;
+                };
+                let (label, value) = &bars[index];
+                tooltip.set_text(Some(&format!("{}: {}", label, format_size(*value, DECIMAL))));
+                true
+            }),
+        );
+
+        let click = gtk::GestureClick::new();
+        click.connect_released(clone!(@strong bars => move |_gesture, _n, _x, y| {
+            let index = bar_at(&bars.borrow(), y);
+            if let Some(index) = index {
+                on_click(index);
+            }
+        }));
+        area.add_controller(&click);
+
+        Self { area, bars }
+    }
+
+    pub fn set_bars(&self, bars: Vec<(String, u64)>) {
+        self.area
+            .set_content_height((bars.len() as f64 * ROW_HEIGHT) as i32);
+        *self.bars.borrow_mut() = bars;
+        self.area.queue_draw();
+    }
+
+    pub fn get_widget(&self) -> gtk::Widget {
+        self.area.clone().upcast()
+    }
+}
@@ -1,2 +1,4 @@
+pub mod bar_chart;
 pub mod go_button;
 pub mod menu_builder;
+pub mod treemap_view;
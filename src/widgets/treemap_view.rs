@@ -0,0 +1,205 @@
+use crate::gtk_prelude::*;
+use crate::treemap::{layout, Cell, Node, Rect, HEADER_HEIGHT};
+use humansize::{format_size, DECIMAL};
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+const FONT_SIZE: f64 = 11.0;
+const SELECTION_COLOR: (f64, f64, f64) = (0.1, 0.1, 0.1);
+
+#[derive(Debug, Default)]
+struct State {
+    nodes: Vec<Node>,
+    /// Layout of `nodes` and bounds it was made for.
+    cells: Option<(Rect, Vec<Cell>)>,
+    selected: Option<PathBuf>,
+}
+
+impl State {
+    /// Lays out nodes within the bounds, unless they are already laid out so.
+    fn update_layout(&mut self, bounds: Rect) {
+        let laid_out = self.cells.as_ref().map(|(laid_out, _)| *laid_out);
+        if laid_out != Some(bounds) {
+            self.cells = Some((bounds, layout(&self.nodes, bounds)));
+        }
+    }
+
+    fn cells(&self) -> &[Cell] {
+        self.cells.as_ref().map_or(&[], |(_, cells)| cells)
+    }
+
+    /// The deepest node drawn at the point.
+    fn node_at(&mut self, bounds: Rect, x: f64, y: f64) -> Option<&Node> {
+        self.update_layout(bounds);
+        let cell = self
+            .cells()
+            .iter()
+            .rev()
+            .find(|cell| cell.rect.contains(x, y))?;
+        Some(cell.node(&self.nodes))
+    }
+}
+
+/// Treemap of directories and files. Files are colored by their group, so copies of the same
+/// file share a color.
+#[derive(Clone, Debug)]
+pub struct TreemapView {
+    area: gtk::DrawingArea,
+    state: Rc<RefCell<State>>,
+}
+
+fn bounds(area: &gtk::DrawingArea) -> Rect {
+    Rect {
+        x: 0.0,
+        y: 0.0,
+        width: area.width() as f64,
+        height: area.height() as f64,
+    }
+}
+
+/// Distinct colors for neighbouring group indices.
+fn group_color(group: usize) -> (f64, f64, f64) {
+    let hue = (group as f64 * 0.618_034).fract() * 6.0;
+    let (saturation, value) = (0.45, 0.9);
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let m = value - chroma;
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    (r + m, g + m, b + m)
+}
+
+fn draw(cr: &gtk::cairo::Context, state: &State) -> Result<(), gtk::cairo::Error> {
+    let cells = state.cells();
+    cr.set_font_size(FONT_SIZE);
+    cr.set_line_width(1.0);
+    for cell in cells {
+        let rect = cell.rect;
+        let node = cell.node(&state.nodes);
+        match node.group {
+            Some(group) => {
+                let (r, g, b) = group_color(group);
+                cr.set_source_rgb(r, g, b);
+            }
+            None => {
+                let shade = 0.95 - 0.05 * cell.depth.min(6) as f64;
+                cr.set_source_rgb(shade, shade, shade);
+            }
+        }
+        cr.rectangle(rect.x, rect.y, rect.width, rect.height);
+        cr.fill_preserve()?;
+        cr.set_source_rgba(0.0, 0.0, 0.0, 0.3);
+        cr.stroke()?;
+
+        if node.group.is_none() && rect.height >= HEADER_HEIGHT {
+            cr.save()?;
+            cr.rectangle(rect.x, rect.y, rect.width, HEADER_HEIGHT);
+            cr.clip();
+            cr.set_source_rgb(0.2, 0.2, 0.2);
+            cr.move_to(rect.x + 3.0, rect.y + HEADER_HEIGHT - 4.0);
+            cr.show_text(&node.name)?;
+            cr.restore()?;
+        }
+    }
+
+    if let Some(selected) = &state.selected {
+        if let Some(cell) = cells
+            .iter()
+            .find(|cell| cell.node(&state.nodes).path == *selected)
+        {
+            let rect = cell.rect;
+            cr.set_source_rgb(SELECTION_COLOR.0, SELECTION_COLOR.1, SELECTION_COLOR.2);
+            cr.set_line_width(3.0);
+            cr.rectangle(
+                rect.x + 1.5,
+                rect.y + 1.5,
+                rect.width - 3.0,
+                rect.height - 3.0,
+            );
+            cr.stroke()?;
+        }
+    }
+    Ok(())
+}
+
+impl TreemapView {
+    /// `on_select` gets paths of files of the clicked cell. `on_activate` is called on double
+    /// click, after `on_select`.
+    pub fn new(
+        on_select: impl Fn(Vec<PathBuf>) + 'static,
+        on_activate: impl Fn() + 'static,
+    ) -> Self {
+        let area = gtk::DrawingArea::builder()
+            .hexpand(true)
+            .vexpand(true)
+            .has_tooltip(true)
+            .build();
+        let state: Rc<RefCell<State>> = Default::default();
+
+        area.set_draw_func(clone!(@strong state => move |area, cr, _width, _height| {
+            let mut state = state.borrow_mut();
+            state.update_layout(bounds(area));
+            if let Err(error) = draw(cr, &state) {
+                eprintln!("Cannot draw a treemap. {}", error);
+            }
+        }));
+
+        area.connect_query_tooltip(
+            clone!(@strong state => move |area, x, y, _keyboard, tooltip| {
+                let mut state = state.borrow_mut();
+                let Some(node) = state.node_at(bounds(area), x as f64, y as f64) else {
+                    return false;
+                };
+                tooltip.set_text(Some(&format!(
+                    "{}\n{} wasted",
+                    node.path.display(),
+                    format_size(node.size, DECIMAL)
+                )));
+                true
+            }),
+        );
+
+        let click = gtk::GestureClick::new();
+        click.connect_pressed(
+            clone!(@strong state, @weak area => move |_gesture, n_press, x, y| {
+                let files = {
+                    let mut state = state.borrow_mut();
+                    let Some(node) = state.node_at(bounds(&area), x, y) else {
+                        return;
+                    };
+                    let files: Vec<PathBuf> = node.files().into_iter().map(PathBuf::from).collect();
+                    let path = node.path.clone();
+                    state.selected = Some(path);
+                    files
+                };
+                area.queue_draw();
+                on_select(files);
+                if n_press == 2 {
+                    on_activate();
+                }
+            }),
+        );
+        area.add_controller(&click);
+
+        Self { area, state }
+    }
+
+    pub fn set_nodes(&self, nodes: Vec<Node>) {
+        let mut state = self.state.borrow_mut();
+        state.nodes = nodes;
+        state.cells = None;
+        state.selected = None;
+        self.area.queue_draw();
+    }
+
+    pub fn get_widget(&self) -> gtk::Widget {
+        self.area.clone().upcast()
+    }
+}